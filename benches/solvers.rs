@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use adv2020_16::{Backtracking, BipartiteMatching, GreedyElimination, Solver};
+
+type Generator = fn(usize) -> Vec<Vec<usize>>;
+
+// Rule `i` fits columns `i..n`, as in the puzzle input, so the last rule is
+// always forced and every strategy succeeds.
+fn staircase(n: usize) -> Vec<Vec<usize>> {
+    (0..n).map(|rule| (rule..n).collect()).collect()
+}
+
+// Rule `i` fits columns `i` and `i + 1`, with the last two rules sharing the
+// last two columns. The assignment is unique, since only rule 0 fits column 0,
+// but no rule is ever down to one column so greedy elimination fails.
+fn chain(n: usize) -> Vec<Vec<usize>> {
+    (0..n)
+        .map(|rule| {
+            let first = rule.min(n - 2);
+            vec![first, first + 1]
+        })
+        .collect()
+}
+
+fn solvers(c: &mut Criterion) {
+    let strategies: [(&str, &dyn Solver); 3] = [
+        ("greedy", &GreedyElimination),
+        ("matching", &BipartiteMatching),
+        ("backtracking", &Backtracking),
+    ];
+    let inputs: [(&str, Generator); 2] = [("staircase", staircase), ("chain", chain)];
+
+    for (input, generate) in inputs.iter() {
+        let mut group = c.benchmark_group(format!("solvers/{}", input));
+        for n in [20, 100, 400].iter() {
+            let candidates = generate(*n);
+            for (name, solver) in strategies.iter() {
+                group.bench_with_input(BenchmarkId::new(*name, n), &candidates, |b, candidates| {
+                    b.iter(|| solver.solve(black_box(candidates)))
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, solvers);
+criterion_main!(benches);
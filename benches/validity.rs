@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use adv2020_16::{
+    filter_invalid, find_all_invalid_fields, find_all_valid_positions,
+    find_all_valid_positions_cached, find_invalid_fields, Notes,
+};
+
+fn valid_positions(c: &mut Criterion) {
+    let notes = Notes::parse(include_str!("../input.txt")).unwrap();
+    let num_columns = notes.num_columns();
+    let rules = notes.rules;
+    let valid_passports = filter_invalid(notes.near_passports, &rules);
+
+    let mut group = c.benchmark_group("find_all_valid_positions");
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            find_all_valid_positions(black_box(&rules), black_box(&valid_passports), num_columns)
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            find_all_valid_positions_cached(
+                black_box(&rules),
+                black_box(&valid_passports),
+                num_columns,
+            )
+        })
+    });
+    group.finish();
+}
+
+fn invalid_fields(c: &mut Criterion) {
+    let notes = Notes::parse(include_str!("../input.txt")).unwrap();
+    let rules = notes.rules;
+    let passports = notes.near_passports;
+
+    let mut group = c.benchmark_group("find_all_invalid_fields");
+    group.bench_function("combined_coverage", |b| {
+        b.iter(|| find_all_invalid_fields(black_box(&passports), black_box(&rules)))
+    });
+    group.bench_function("per_rule", |b| {
+        b.iter(|| {
+            black_box(&passports)
+                .iter()
+                .flat_map(|passport| find_invalid_fields(passport, black_box(&rules)))
+                .collect::<Vec<&i64>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, valid_positions, invalid_fields);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+use crate::Rule;
+
+// Rules spanning more values than this fall back to the range checks.
+const MAX_TABLE_SIZE: i128 = 1 << 20;
+
+/// A rule with its valid values tabulated once so that repeated lookups are a
+/// single index rather than two range checks.
+#[derive(Debug, Clone)]
+pub struct CachedRule<'a> {
+    rule: &'a Rule,
+    low: i64,
+    table: Option<Vec<bool>>,
+}
+
+impl<'a> CachedRule<'a> {
+    pub fn new(rule: &'a Rule) -> CachedRule<'a> {
+        let low = *rule.range1.start().min(rule.range2.start());
+        let high = *rule.range1.end().max(rule.range2.end());
+        let size = high as i128 - low as i128 + 1;
+        let table = if size > 0 && size <= MAX_TABLE_SIZE {
+            Some((low..=high).map(|value| rule.valid(&value)).collect())
+        } else {
+            None
+        };
+        CachedRule { rule, low, table }
+    }
+
+    pub fn valid(&self, field: &i64) -> bool {
+        match &self.table {
+            Some(table) => {
+                let index = *field as i128 - self.low as i128;
+                index >= 0 && (index as usize) < table.len() && table[index as usize]
+            }
+            None => self.rule.valid(field),
+        }
+    }
+}
+
+/// As `find_all_valid_positions`, but looks values up in a `CachedRule` table.
+pub fn find_all_valid_positions_cached(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+) -> Vec<Vec<(usize, String)>> {
+    let mut positions = Vec::new();
+    for rule in rules {
+        let cached = CachedRule::new(rule);
+        let mut rule_positions = Vec::new();
+        for position in 0..num_columns {
+            if passports
+                .iter()
+                .all(|passport| cached.valid(&passport[position]))
+            {
+                rule_positions.push((position, rule.name.clone()));
+            }
+        }
+        positions.push(rule_positions);
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{filter_invalid, find_all_valid_positions, Notes};
+
+    #[test]
+    fn cached_lookups_match_the_rule() {
+        let rule = Rule::new("class".to_string(), 1..=3, 5..=7);
+        let cached = CachedRule::new(&rule);
+        for value in -2..10 {
+            assert_eq!(cached.valid(&value), rule.valid(&value), "value {}", value);
+        }
+    }
+
+    #[test]
+    fn wide_rules_fall_back_to_ranges() {
+        let rule = Rule::new("wide".to_string(), 0..=3, 5..=i64::MAX);
+        let cached = CachedRule::new(&rule);
+        assert!(cached.table.is_none());
+        assert!(cached.valid(&i64::MAX));
+        assert!(!cached.valid(&4));
+    }
+
+    #[test]
+    fn cached_positions_match_uncached_positions() {
+        let notes = Notes::parse(include_str!("../input.txt")).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert_eq!(
+            find_all_valid_positions_cached(&notes.rules, &valid_passports, notes.num_columns()),
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns())
+        );
+    }
+}
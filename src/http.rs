@@ -0,0 +1,64 @@
+use crate::{Error, Notes};
+
+/// Whether `path` is an `http://` or `https://` URL rather than a file.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Downloads the text at `url`.
+pub fn fetch(url: &str) -> Result<String, Error> {
+    fetch_with(url, |url| {
+        ureq::get(url)
+            .call()
+            .map_err(|err| err.to_string())?
+            .into_string()
+            .map_err(|err| err.to_string())
+    })
+}
+
+/// As `fetch`, but downloading with `get` so that it can be replaced in tests.
+pub fn fetch_with(
+    url: &str,
+    get: impl FnOnce(&str) -> Result<String, String>,
+) -> Result<String, Error> {
+    get(url).map_err(Error::Http)
+}
+
+impl Notes {
+    /// Downloads and parses the notes at `url`.
+    pub fn read_url(url: &str) -> Result<Notes, Error> {
+        Ok(Notes::parse(&fetch(url)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/input.txt";
+
+    #[test]
+    fn it_recognises_urls() {
+        assert!(is_url(URL));
+        assert!(is_url("http://localhost:8080/input"));
+        assert!(!is_url("input.txt"));
+        assert!(!is_url("-"));
+    }
+
+    #[test]
+    fn fetch_and_parse_errors_are_distinct() {
+        let body = fetch_with(URL, |_| Ok(include_str!("../input.txt").to_string())).unwrap();
+        assert_eq!(Notes::parse(&body).unwrap().rules.len(), 20);
+
+        match fetch_with(URL, |_| Err("status code 404".to_string())) {
+            Err(err @ Error::Http(_)) => {
+                assert_eq!(err.to_string(), "could not fetch input: status code 404")
+            }
+            other => panic!("expected a fetch error, got {:?}", other),
+        }
+
+        let body = fetch_with(URL, |_| Ok("<html></html>".to_string())).unwrap();
+        let err: Error = Notes::parse(&body).unwrap_err().into();
+        assert!(matches!(err, Error::Parse(_)));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{determine_field_positions, find_all_valid_positions, find_invalid_fields};
+use crate::{Rule, SolveError};
+
+/// Candidate columns for each rule, narrowed one nearby ticket at a time so
+/// that an interactive tool need not re-run the whole pipeline per ticket.
+#[derive(Debug, Clone)]
+pub struct SolverState {
+    rules: Vec<Rule>,
+    // One column for each rule, as in `Notes::num_columns`.
+    num_columns: usize,
+    passports: Vec<Vec<i64>>,
+    candidates: Vec<Vec<usize>>,
+}
+
+impl SolverState {
+    pub fn new(rules: Vec<Rule>) -> SolverState {
+        let num_columns = rules.len();
+        let columns: Vec<usize> = (0..num_columns).collect();
+        SolverState {
+            candidates: vec![columns; rules.len()],
+            num_columns,
+            passports: Vec::new(),
+            rules,
+        }
+    }
+
+    pub fn candidates(&self) -> &[Vec<usize>] {
+        &self.candidates
+    }
+
+    /// Narrows the candidates using a new ticket and re-solves. Tickets with a
+    /// value no rule accepts are discarded, as in part two, and leave the
+    /// candidates unchanged. A ticket without one field for each column fails
+    /// with `SolveError::TicketLength`, numbered among the tickets kept so far,
+    /// and is not added.
+    pub fn add_ticket(&mut self, passport: &[i64]) -> Result<Vec<(usize, String)>, SolveError> {
+        if passport.len() != self.num_columns {
+            return Err(SolveError::TicketLength {
+                ticket: self.passports.len(),
+                expected: self.num_columns,
+                found: passport.len(),
+            });
+        }
+        if find_invalid_fields(passport, &self.rules).is_empty() {
+            for (rule, columns) in self.rules.iter().zip(self.candidates.iter_mut()) {
+                columns.retain(|column| rule.valid(&passport[*column]));
+            }
+            self.passports.push(passport.to_vec());
+        }
+        self.solve()
+    }
+
+    pub fn solve(&self) -> Result<Vec<(usize, String)>, SolveError> {
+        determine_field_positions(
+            self.rules
+                .iter()
+                .zip(&self.candidates)
+                .map(|(rule, columns)| {
+                    columns
+                        .iter()
+                        .map(|column| (*column, rule.name.clone()))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Rebuilds the candidates from every ticket added so far and solves them
+    /// from scratch.
+    pub fn resolve(&mut self) -> Result<Vec<(usize, String)>, SolveError> {
+        self.candidates = find_all_valid_positions(&self.rules, &self.passports, self.num_columns)
+            .into_iter()
+            .map(|positions| positions.into_iter().map(|(column, _)| column).collect())
+            .collect();
+        self.solve()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{solve, Notes};
+
+    use indoc::indoc;
+
+    const TEST_INPUT_TWO: &str = indoc! {"
+        class: 0-1 or 4-19
+        row: 0-5 or 8-19
+        seat: 0-13 or 16-19
+
+        your ticket:
+        11,12,13
+
+        nearby tickets:
+        3,9,18
+        15,1,5
+        5,14,9
+    "};
+
+    #[test]
+    fn incremental_solve_matches_batch_solve() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let mut state = SolverState::new(notes.rules.clone());
+
+        assert_eq!(state.add_ticket(&[3, 9, 18]), Err(SolveError::Ambiguous));
+        assert_eq!(state.add_ticket(&[15, 1, 5]), Err(SolveError::Ambiguous));
+        assert_eq!(state.add_ticket(&[3, 99, 18]), Err(SolveError::Ambiguous));
+        let incremental = state.add_ticket(&[5, 14, 9]).unwrap();
+
+        assert_eq!(incremental, solve(&notes).unwrap());
+        assert_eq!(state.resolve().unwrap(), incremental);
+        assert_eq!(state.candidates(), &[vec![1, 2], vec![0, 1, 2], vec![2]]);
+    }
+
+    #[test]
+    fn it_rejects_a_ticket_of_the_wrong_width() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let mut state = SolverState::new(notes.rules);
+        state.add_ticket(&[3, 9, 18]).unwrap_err();
+        for ticket in &[vec![15, 1], vec![15, 1, 5, 7]] {
+            assert_eq!(
+                state.add_ticket(ticket),
+                Err(SolveError::TicketLength {
+                    ticket: 1,
+                    expected: 3,
+                    found: ticket.len(),
+                })
+            );
+        }
+        assert_eq!(
+            state.candidates(),
+            &[vec![1, 2], vec![0, 1, 2], vec![0, 1, 2]]
+        );
+    }
+}
@@ -0,0 +1,4011 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::ops::RangeInclusive;
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+mod cache;
+#[cfg(feature = "http")]
+mod http;
+mod incremental;
+mod output;
+mod range_set;
+mod samples;
+mod solver;
+mod steps;
+mod timing;
+
+pub use cache::{find_all_valid_positions_cached, CachedRule};
+#[cfg(feature = "http")]
+pub use http::{fetch, fetch_with, is_url};
+pub use incremental::SolverState;
+pub use output::{format_solution, format_solution_csv, format_solution_json, FormatOptions};
+pub use range_set::RangeSet;
+pub use samples::{sample_answers, SampleAnswers};
+pub use solver::{
+    all_assignments, Backtracking, BipartiteMatching, GreedyElimination, SolveError, Solver,
+    StrictChain,
+};
+pub use steps::{solve_steps, SolveStep};
+pub use timing::{solve_timed, SolveTimings};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rule {
+    pub name: String,
+    pub range1: RangeInclusive<i64>,
+    pub range2: RangeInclusive<i64>,
+    /// An optional weight, written as a trailing `[w=N]`, for experimental
+    /// solvers to break ties with. The built in solvers ignore it.
+    pub weight: Option<i64>,
+}
+
+impl Rule {
+    pub fn new(name: String, range1: RangeInclusive<i64>, range2: RangeInclusive<i64>) -> Rule {
+        Rule {
+            name,
+            range1,
+            range2,
+            weight: None,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: i64) -> Rule {
+        self.weight = Some(weight);
+        self
+    }
+
+    pub fn valid(&self, field: &i64) -> bool {
+        self.range1.contains(field) || self.range2.contains(field)
+    }
+
+    /// The number of distinct values the rule accepts. A `u128` is used so that
+    /// rules spanning the whole of `i64` are counted without overflow.
+    pub fn coverage(&self) -> u128 {
+        self.range_set().len()
+    }
+
+    pub fn range_set(&self) -> RangeSet {
+        RangeSet::from_ranges(&[self.range1.clone(), self.range2.clone()])
+    }
+
+    /// Whether any value is accepted by both rules.
+    pub fn overlaps(&self, other: &Rule) -> bool {
+        !self.range_set().intersection(&other.range_set()).is_empty()
+    }
+
+    /// The values between the rule's two ranges which it does not accept, such
+    /// as 4 for `1-3 or 5-7`. A gap of a single value is often a typo.
+    pub fn internal_gaps(&self) -> Vec<RangeInclusive<i64>> {
+        self.range_set().gaps()
+    }
+
+    /// The share of the values accepted by either rule which both accept, from
+    /// 0.0 for disjoint rules to 1.0 for rules accepting the same values.
+    pub fn jaccard(&self, other: &Rule) -> f64 {
+        let (this, other) = (self.range_set(), other.range_set());
+        let union = this.union(&other).len();
+        if union == 0 {
+            return 1.0;
+        }
+        this.intersection(&other).len() as f64 / union as f64
+    }
+
+    /// The values accepted by exactly one of the two rules, as sorted disjoint
+    /// ranges.
+    pub fn symmetric_difference(&self, other: &Rule) -> Vec<RangeInclusive<i64>> {
+        self.range_set()
+            .symmetric_difference(&other.range_set())
+            .ranges()
+            .to_vec()
+    }
+
+    /// The values accepted by both rules, as sorted disjoint ranges.
+    pub fn intersection(&self, other: &Rule) -> Vec<RangeInclusive<i64>> {
+        self.range_set()
+            .intersection(&other.range_set())
+            .ranges()
+            .to_vec()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}-{} or {}-{}",
+            self.name,
+            self.range1.start(),
+            self.range1.end(),
+            self.range2.start(),
+            self.range2.end()
+        )?;
+        if let Some(weight) = self.weight {
+            write!(f, " [w={}]", weight)?;
+        }
+        Ok(())
+    }
+}
+
+// Rules sort by name, with the ranges and then the weight breaking ties
+// between rules of the same name.
+impl Ord for Rule {
+    fn cmp(&self, other: &Rule) -> Ordering {
+        let bounds = |rule: &Rule| {
+            (
+                (*rule.range1.start(), *rule.range1.end()),
+                (*rule.range2.start(), *rule.range2.end()),
+            )
+        };
+        self.name
+            .cmp(&other.name)
+            .then_with(|| bounds(self).cmp(&bounds(other)))
+            .then_with(|| self.weight.cmp(&other.weight))
+    }
+}
+
+impl PartialOrd for Rule {
+    fn partial_cmp(&self, other: &Rule) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// An error reading the notes. Every variant carries the 1-based number of the
+/// line at which it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    MissingSection {
+        section: &'static str,
+        line: usize,
+    },
+    InvalidNumber {
+        value: String,
+        line: usize,
+    },
+    EmptyField {
+        line: usize,
+    },
+    /// A line in strict mode which is not in the form its section expects.
+    UnexpectedLine {
+        text: String,
+        line: usize,
+    },
+    /// The rule count or a ticket's length differs from a `fields: N` header.
+    FieldCount {
+        expected: usize,
+        found: usize,
+        line: usize,
+    },
+    /// `wrapped_tickets` was set without a `section_marker`, so the blank lines
+    /// ending each ticket would also end its section.
+    WrappedWithoutMarker {
+        line: usize,
+    },
+}
+
+impl ParseError {
+    pub fn line(&self) -> usize {
+        match self {
+            ParseError::MissingSection { line, .. } => *line,
+            ParseError::InvalidNumber { line, .. } => *line,
+            ParseError::EmptyField { line } => *line,
+            ParseError::UnexpectedLine { line, .. } => *line,
+            ParseError::FieldCount { line, .. } => *line,
+            ParseError::WrappedWithoutMarker { line } => *line,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingSection { section, line } => {
+                write!(f, "line {}: missing {} section", line, section)
+            }
+            ParseError::InvalidNumber { value, line } => {
+                write!(f, "line {}: invalid number {}", line, value)
+            }
+            ParseError::EmptyField { line } => write!(f, "line {}: empty field", line),
+            ParseError::UnexpectedLine { text, line } => {
+                write!(f, "line {}: unexpected line {:?}", line, text)
+            }
+            ParseError::FieldCount {
+                expected,
+                found,
+                line,
+            } => write!(
+                f,
+                "line {}: expected {} fields, found {}",
+                line, expected, found
+            ),
+            ParseError::WrappedWithoutMarker { line } => {
+                write!(f, "line {}: wrapped tickets need a section marker", line)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    DuplicateName(String),
+    InvalidRange {
+        rule: String,
+        range: RangeInclusive<i64>,
+    },
+    TicketLength {
+        ticket: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// One of my tickets has a different number of fields to the rules.
+    MyTicketLength {
+        ticket: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::DuplicateName(name) => write!(f, "rule {} is defined twice", name),
+            ValidationError::InvalidRange { rule, range } => write!(
+                f,
+                "rule {} has an empty range {}-{}",
+                rule,
+                range.start(),
+                range.end()
+            ),
+            ValidationError::TicketLength {
+                ticket,
+                expected,
+                found,
+            } => write!(
+                f,
+                "nearby ticket {} has {} fields, expected {}",
+                ticket + 1,
+                found,
+                expected
+            ),
+            ValidationError::MyTicketLength {
+                ticket,
+                expected,
+                found,
+            } => write!(
+                f,
+                "your ticket {} has {} fields, expected {}",
+                ticket + 1,
+                found,
+                expected
+            ),
+        }
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// Why two sets of notes could not be merged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    RuleCount {
+        expected: usize,
+        found: usize,
+    },
+    /// The rules at the same position differ, named as in the first notes.
+    DifferentRule(String),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::RuleCount { expected, found } => {
+                write!(f, "expected {} rules, found {}", expected, found)
+            }
+            MergeError::DifferentRule(name) => write!(f, "rule {} differs", name),
+        }
+    }
+}
+
+impl error::Error for MergeError {}
+
+/// An error loading or solving notes: the input could not be read from a file
+/// or stdin, fetched over HTTP, or parsed, or the parsed notes could not be
+/// solved.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(ParseError),
+    Solve(SolveError),
+    /// The input could not be downloaded. Only the `http` feature fetches
+    /// input, but the variant is always present so that enabling the feature
+    /// does not change the type.
+    Http(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "could not read input: {}", err),
+            Error::Parse(err) => write!(f, "could not parse input: {}", err),
+            Error::Solve(err) => write!(f, "could not determine fields: {}", err),
+            Error::Http(err) => write!(f, "could not fetch input: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::Solve(err) => Some(err),
+            Error::Http(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Error {
+        Error::Parse(err)
+    }
+}
+
+impl From<SolveError> for Error {
+    fn from(err: SolveError) -> Error {
+        Error::Solve(err)
+    }
+}
+
+/// Joins a rules document and a tickets document, holding the `your ticket:`
+/// and `nearby tickets:` sections, into a single input. Line numbers in parse
+/// errors then count from the start of the rules.
+pub fn stitch_input(rules: &str, tickets: &str) -> String {
+    format!("{}\n\n{}", rules.trim_end(), tickets.trim_start())
+}
+
+/// Reads the text of the file at `path`, or of stdin when `path` is `-`.
+pub fn read_input(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        Ok(input)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// The expressions recognising rule and ticket lines, for inputs in a slightly
+/// different format. A rule pattern must capture `name`, `range_1_low` and
+/// `range_1_high`, and may capture the same for `range_2` and a `range_N_sep`
+/// to allow exclusive `..` ranges. Ticket lines are those the field pattern
+/// matches.
+#[derive(Debug, Clone)]
+pub struct Patterns {
+    pub rule: Regex,
+    pub field: Regex,
+    /// Read numbers with a `0x` prefix as hexadecimal. Other numbers are still
+    /// decimal, so the two may be mixed.
+    pub hex: bool,
+}
+
+impl Patterns {
+    /// The default patterns, also accepting hexadecimal bounds and values.
+    pub fn hex() -> Patterns {
+        Patterns {
+            rule: HEX_RULE_REGEX.clone(),
+            field: FIELD_REGEX.clone(),
+            hex: true,
+        }
+    }
+}
+
+impl Default for Patterns {
+    fn default() -> Patterns {
+        Patterns {
+            rule: RULE_REGEX.clone(),
+            field: FIELD_REGEX.clone(),
+            hex: false,
+        }
+    }
+}
+
+/// Controls how an input document is split into its three sections.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Lines starting with this marker open a new section, e.g. `[` for
+    /// `[RULES]`, `[MY]` and `[NEARBY]` or `\x0c` for form-feeds. `None` means
+    /// sections are separated by blank lines.
+    pub section_marker: Option<String>,
+    pub patterns: Patterns,
+    /// Tickets may be wrapped across several lines, with a blank line ending
+    /// each one. Blank lines already separate sections unless there is a
+    /// `section_marker`, so parsing fails with
+    /// `ParseError::WrappedWithoutMarker` when this is set without one.
+    pub wrapped_tickets: bool,
+    /// Fail on any line in a section which is neither blank nor in the
+    /// expected form, instead of quietly ending the section there.
+    pub strict: bool,
+}
+
+impl ParseOptions {
+    pub fn with_marker(marker: &str) -> ParseOptions {
+        ParseOptions {
+            section_marker: Some(marker.to_string()),
+            ..ParseOptions::default()
+        }
+    }
+
+    fn keeps_blank_lines(&self) -> bool {
+        self.wrapped_tickets && self.section_marker.is_some()
+    }
+
+    // Reads the tickets in a section, each with the index of the line it
+    // starts on.
+    fn read_tickets(
+        &self,
+        section: &[(usize, &str)],
+    ) -> Result<Vec<(usize, Vec<i64>)>, ParseError> {
+        if self.wrapped_tickets {
+            return read_wrapped_passports(section, &self.patterns);
+        }
+        let passports = read_passports_with(&mut section.iter().copied(), &self.patterns)?;
+        // Ticket lines follow the section header, as in read_passports.
+        Ok(section
+            .iter()
+            .skip(1)
+            .map(|(index, _)| *index)
+            .zip(passports)
+            .collect())
+    }
+
+    // In strict mode, fails on the first line after `skip` which is neither
+    // blank nor matched by `pattern`.
+    fn check_lines(
+        &self,
+        lines: &[(usize, &str)],
+        skip: usize,
+        pattern: &Regex,
+    ) -> Result<(), ParseError> {
+        if !self.strict {
+            return Ok(());
+        }
+        match lines
+            .iter()
+            .skip(skip)
+            .find(|(_, line)| !line.trim().is_empty() && !pattern.is_match(line))
+        {
+            Some((index, line)) => Err(ParseError::UnexpectedLine {
+                text: line.to_string(),
+                line: index + 1,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn is_marker(&self, line: &str) -> bool {
+        match &self.section_marker {
+            Some(marker) => line.starts_with(marker.as_str()),
+            None => false,
+        }
+    }
+
+    // Splits the numbered input lines into sections. Marker lines are kept as
+    // the first line of the section they open so they take the place of the
+    // section header.
+    fn split_sections<'a>(
+        &self,
+        lines: impl Iterator<Item = &'a str>,
+    ) -> Vec<Vec<(usize, &'a str)>> {
+        let mut sections: Vec<Vec<(usize, &str)>> = Vec::new();
+        let mut current = Vec::new();
+        for (index, line) in lines.enumerate() {
+            // Some editors start UTF-8 files with a byte order mark, which would
+            // otherwise end up in the first rule's name.
+            let line = if index == 0 {
+                line.strip_prefix('\u{feff}').unwrap_or(line)
+            } else {
+                line
+            };
+            let blank = line.trim().is_empty();
+            let marker = self.is_marker(line);
+            if marker || (blank && self.section_marker.is_none()) {
+                if !current.is_empty() {
+                    sections.push(current);
+                }
+                current = Vec::new();
+            }
+            if marker || !blank || self.keeps_blank_lines() {
+                current.push((index, line));
+            }
+        }
+        if !current.is_empty() {
+            sections.push(current);
+        }
+        sections
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Notes {
+    pub rules: Vec<Rule>,
+    /// Usually a single ticket, but batches of personal tickets may share the
+    /// same rules and nearby tickets.
+    pub my_passports: Vec<Vec<i64>>,
+    pub near_passports: Vec<Vec<i64>>,
+}
+
+impl Notes {
+    pub fn parse(input: &str) -> Result<Notes, ParseError> {
+        Notes::parse_with(input, &ParseOptions::default())
+    }
+
+    pub fn parse_with(input: &str, options: &ParseOptions) -> Result<Notes, ParseError> {
+        Notes::read(input.lines(), options)
+    }
+
+    /// Reads and parses the notes in the file at `path`, or on stdin when
+    /// `path` is `-`.
+    pub fn read_from(path: &str) -> Result<Notes, Error> {
+        Ok(Notes::parse(&read_input(path)?)?)
+    }
+
+    /// Parses notes which have already been split into lines, such as a
+    /// `Vec<&str>` or `&[String]`.
+    pub fn from_lines<S: AsRef<str>>(lines: &[S]) -> Result<Notes, ParseError> {
+        Notes::from_lines_with(lines, &ParseOptions::default())
+    }
+
+    pub fn from_lines_with<S: AsRef<str>>(
+        lines: &[S],
+        options: &ParseOptions,
+    ) -> Result<Notes, ParseError> {
+        Notes::read(lines.iter().map(|line| line.as_ref()), options)
+    }
+
+    fn read<'a>(
+        lines: impl Iterator<Item = &'a str>,
+        options: &ParseOptions,
+    ) -> Result<Notes, ParseError> {
+        if options.wrapped_tickets && options.section_marker.is_none() {
+            return Err(ParseError::WrappedWithoutMarker { line: 1 });
+        }
+        // Anything after an END line is ignored.
+        let sections = options.split_sections(lines.take_while(|line| !is_end_marker(line)));
+        let mut sections = sections.iter();
+
+        let missing_rules = ParseError::MissingSection {
+            section: "rules",
+            line: 1,
+        };
+        let mut rule_lines = sections.next().ok_or_else(|| missing_rules.clone())?;
+        // An optional `fields: N` header declares how many fields the rules and
+        // tickets should have. It may sit in a paragraph of its own.
+        let declared = match rule_lines.iter().find(|(_, line)| !options.is_marker(line)) {
+            Some((index, line)) => match FIELDS_REGEX.captures(line) {
+                Some(caps) => Some(parse_count(&caps["count"], *index)?),
+                None => None,
+            },
+            None => None,
+        };
+        if declared.is_some()
+            && rule_lines
+                .iter()
+                .all(|(_, line)| !options.patterns.rule.is_match(line))
+        {
+            rule_lines = sections.next().ok_or(missing_rules)?;
+        }
+        let rule_lines: Vec<(usize, &str)> = rule_lines
+            .iter()
+            .copied()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .skip_while(|(_, line)| options.is_marker(line) || FIELDS_REGEX.is_match(line))
+            .collect();
+        options.check_lines(&rule_lines, 0, &options.patterns.rule)?;
+        let rules = read_rules_with(&mut rule_lines.iter().copied(), &options.patterns)?;
+
+        // The ticket sections are told apart by their headers so they may come
+        // in either order. Unrecognised headers fill the remaining sections in
+        // the usual order.
+        let mut my_lines = None;
+        let mut near_lines = None;
+        let mut end = line_after(&rule_lines);
+        for section in sections {
+            end = line_after(section);
+            match section_kind(section) {
+                Some(SectionKind::Nearby) if near_lines.is_none() => near_lines = Some(section),
+                Some(SectionKind::Mine) if my_lines.is_none() => my_lines = Some(section),
+                _ if my_lines.is_none() => my_lines = Some(section),
+                _ if near_lines.is_none() => near_lines = Some(section),
+                _ => {}
+            }
+        }
+
+        let my_lines = my_lines.ok_or(ParseError::MissingSection {
+            section: "your ticket",
+            line: end,
+        })?;
+        options.check_lines(my_lines, 1, &options.patterns.field)?;
+        let my_tickets = options.read_tickets(my_lines)?;
+        if my_tickets.is_empty() {
+            return Err(ParseError::MissingSection {
+                section: "your ticket",
+                line: line_after(my_lines),
+            });
+        }
+
+        let near_lines = near_lines.ok_or(ParseError::MissingSection {
+            section: "nearby tickets",
+            line: end,
+        })?;
+        options.check_lines(near_lines, 1, &options.patterns.field)?;
+        let near_tickets = options.read_tickets(near_lines)?;
+
+        if let Some(expected) = declared {
+            if rules.len() != expected {
+                return Err(ParseError::FieldCount {
+                    expected,
+                    found: rules.len(),
+                    line: line_after(&rule_lines),
+                });
+            }
+            for (index, passport) in my_tickets.iter().chain(&near_tickets) {
+                if passport.len() != expected {
+                    return Err(ParseError::FieldCount {
+                        expected,
+                        found: passport.len(),
+                        line: index + 1,
+                    });
+                }
+            }
+        }
+
+        Ok(Notes {
+            rules,
+            my_passports: my_tickets
+                .into_iter()
+                .map(|(_, passport)| passport)
+                .collect(),
+            near_passports: near_tickets
+                .into_iter()
+                .map(|(_, passport)| passport)
+                .collect(),
+        })
+    }
+
+    /// The first, and usually only, personal ticket, or `None` when
+    /// `my_passports` has been emptied.
+    pub fn my_passport(&self) -> Option<&[i64]> {
+        self.my_passports.first().map(Vec::as_slice)
+    }
+
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// The number of fields every ticket should have, one for each rule. The
+    /// solvers work over this many columns and reject nearby tickets of any
+    /// other width.
+    pub fn num_columns(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// The first rule with the given name.
+    pub fn rule_by_name(&self, name: &str) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    /// Writes the notes back out in the standard input format.
+    pub fn to_input_string(&self) -> String {
+        let mut output = String::new();
+        for rule in &self.rules {
+            output.push_str(&format!("{}\n", rule));
+        }
+        output.push_str("\nyour ticket:\n");
+        for passport in &self.my_passports {
+            output.push_str(&format_passport(passport));
+            output.push('\n');
+        }
+        output.push_str("\nnearby tickets:\n");
+        for passport in &self.near_passports {
+            output.push_str(&format_passport(passport));
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Parses the single line form `rules|my tickets|nearby tickets`, with
+    /// rules and tickets separated by `;` and rules written without spaces
+    /// around their ranges, as in
+    /// `class:1-3or5-7;row:6-11or33-44|7,1,14|7,3,47;40,4,50`. Lines in any
+    /// error refer to the expanded standard form.
+    pub fn from_compact(input: &str) -> Result<Notes, ParseError> {
+        let mut sections = input.trim().split('|');
+        let mut items = || {
+            sections
+                .next()
+                .map_or(Vec::new(), |section| section.split(';').collect::<Vec<_>>())
+        };
+        let mut lines: Vec<String> = items()
+            .into_iter()
+            .map(|rule| match rule.find(':') {
+                Some(colon) => format!(
+                    "{}: {}",
+                    &rule[..colon],
+                    rule[colon + 1..]
+                        .replace("or", " or ")
+                        .replace("[w=", " [w=")
+                ),
+                None => rule.to_string(),
+            })
+            .collect();
+        lines.push(String::new());
+        lines.push("your ticket:".to_string());
+        lines.extend(items().into_iter().map(str::to_string));
+        lines.push(String::new());
+        lines.push("nearby tickets:".to_string());
+        lines.extend(items().into_iter().map(str::to_string));
+        Notes::from_lines(&lines)
+    }
+
+    /// Writes the notes in the single line form read by `from_compact`.
+    pub fn to_compact(&self) -> String {
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(|rule| {
+                // Only the ranges are packed; the name may itself contain
+                // spaces or " or ".
+                let line = rule.to_string();
+                let ranges = &line[rule.name.len() + 2..];
+                format!(
+                    "{}:{}",
+                    rule.name,
+                    ranges.replace(" or ", "or").replace(" [w=", "[w=")
+                )
+            })
+            .collect();
+        let tickets = |passports: &[Vec<i64>]| {
+            passports
+                .iter()
+                .map(|passport| format_passport(passport))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        format!(
+            "{}|{}|{}",
+            rules.join(";"),
+            tickets(&self.my_passports),
+            tickets(&self.near_passports)
+        )
+    }
+
+    /// Checks the notes for problems which would stop them from being solved,
+    /// returning every problem found rather than just the first.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+
+        let mut names = HashSet::new();
+        for rule in &self.rules {
+            if !names.insert(rule.name.as_str()) {
+                errors.push(ValidationError::DuplicateName(rule.name.clone()));
+            }
+            for range in [&rule.range1, &rule.range2].iter() {
+                if range.is_empty() {
+                    errors.push(ValidationError::InvalidRange {
+                        rule: rule.name.clone(),
+                        range: (*range).clone(),
+                    });
+                }
+            }
+        }
+
+        for (ticket, passport) in self.my_passports.iter().enumerate() {
+            if passport.len() != self.rules.len() {
+                errors.push(ValidationError::MyTicketLength {
+                    ticket,
+                    expected: self.rules.len(),
+                    found: passport.len(),
+                });
+            }
+        }
+
+        for (ticket, passport) in self.near_passports.iter().enumerate() {
+            if passport.len() != self.rules.len() {
+                errors.push(ValidationError::TicketLength {
+                    ticket,
+                    expected: self.rules.len(),
+                    found: passport.len(),
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Pools the nearby tickets of two sets of notes with identical rules,
+    /// keeping my tickets from `self`.
+    pub fn merge(mut self, other: Notes) -> Result<Notes, MergeError> {
+        if self.rules.len() != other.rules.len() {
+            return Err(MergeError::RuleCount {
+                expected: self.rules.len(),
+                found: other.rules.len(),
+            });
+        }
+        if let Some((rule, _)) = self
+            .rules
+            .iter()
+            .zip(&other.rules)
+            .find(|(rule, other_rule)| rule != other_rule)
+        {
+            return Err(MergeError::DifferentRule(rule.name.clone()));
+        }
+        self.near_passports.extend(other.near_passports);
+        Ok(self)
+    }
+
+    /// Checks that no rule has a bound above `max`, returning the names of the
+    /// rules which do.
+    pub fn assert_bounds(&self, max: i64) -> Result<(), Vec<String>> {
+        let names: Vec<String> = self
+            .rules
+            .iter()
+            .filter(|rule| {
+                [&rule.range1, &rule.range2]
+                    .iter()
+                    .any(|range| *range.start() > max || *range.end() > max)
+            })
+            .map(|rule| rule.name.clone())
+            .collect();
+        if names.is_empty() {
+            Ok(())
+        } else {
+            Err(names)
+        }
+    }
+}
+
+// Bounds may be negative, using either a hyphen or a unicode minus sign.
+const NUMBER: &str = r"[-−]?\d+";
+// As NUMBER, also allowing hexadecimal with a `0x` prefix.
+const HEX_NUMBER: &str = r"[-−]?(?:0x[0-9a-fA-F]+|\d+)";
+// Bounds are separated by a hyphen, en-dash, em-dash or unicode minus sign, or
+// by Rust's `..=` and exclusive `..` range syntax.
+const SEPARATOR: &str = r"\.\.=|\.\.|[-–—−]";
+
+#[cfg(feature = "bincode")]
+impl Notes {
+    /// Serializes the notes to a compact binary blob for caching.
+    pub fn to_bincode(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn from_bincode(bytes: &[u8]) -> Result<Notes, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+fn rule_pattern(number: &str) -> String {
+    format!(
+        r"(?P<name>[\w\s]+): (?P<range_1_low>{n})(?P<range_1_sep>{s})(?P<range_1_high>{n})(?: or (?P<range_2_low>{n})(?P<range_2_sep>{s})(?P<range_2_high>{n}))?(?: \[w=(?P<weight>{n})\])?$",
+        n = number,
+        s = SEPARATOR
+    )
+}
+
+lazy_static! {
+    static ref RULE_REGEX: Regex = Regex::new(&rule_pattern(NUMBER)).unwrap();
+    static ref HEX_RULE_REGEX: Regex = Regex::new(&rule_pattern(HEX_NUMBER)).unwrap();
+    static ref PACKED_RANGE_REGEX: Regex =
+        Regex::new(&format!(r":\s*{n}(?:{s})", n = HEX_NUMBER, s = SEPARATOR)).unwrap();
+    static ref FIELD_REGEX: Regex = Regex::new(r"(?P<value>\d+),?").unwrap();
+    static ref FIELDS_REGEX: Regex = Regex::new(r"^fields:\s*(?P<count>\d+)\s*$").unwrap();
+}
+
+enum SectionKind {
+    Mine,
+    Nearby,
+}
+
+fn section_kind(section: &[(usize, &str)]) -> Option<SectionKind> {
+    let header = section.first()?.1.to_lowercase();
+    if header.contains("nearby") {
+        Some(SectionKind::Nearby)
+    } else if header.contains("your") || header.contains("my") {
+        Some(SectionKind::Mine)
+    } else {
+        None
+    }
+}
+
+/// A borrowed view of a ticket's values, for reading them by field name once
+/// the columns have been solved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ticket<'a> {
+    values: &'a [i64],
+}
+
+impl<'a> Ticket<'a> {
+    pub fn new(values: &'a [i64]) -> Ticket<'a> {
+        Ticket { values }
+    }
+
+    /// Each field name in `assignment` with the ticket's value for it, in
+    /// column order. Fields assigned to a column past the end of the ticket
+    /// are skipped.
+    pub fn iter_named(
+        &self,
+        assignment: &'a [(usize, String)],
+    ) -> impl Iterator<Item = (&'a str, i64)> + 'a {
+        let values = self.values;
+        let mut named: Vec<(usize, &str)> = assignment
+            .iter()
+            .map(|(column, name)| (*column, name.as_str()))
+            .collect();
+        named.sort_unstable();
+        named
+            .into_iter()
+            .filter_map(move |(column, name)| Some((name, *values.get(column)?)))
+    }
+}
+
+/// A ticket in the comma separated form used by the input.
+pub fn format_passport(passport: &[i64]) -> String {
+    passport
+        .iter()
+        .map(|field| field.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Whether the line is the END marker closing an input.
+fn is_end_marker(line: &str) -> bool {
+    line.trim() == "END"
+}
+
+// The 1-based number of the line following a section of numbered lines.
+fn line_after(lines: &[(usize, &str)]) -> usize {
+    lines.last().map_or(1, |(index, _)| index + 2)
+}
+
+fn parse_count(value: &str, index: usize) -> Result<usize, ParseError> {
+    value.parse().map_err(|_| ParseError::InvalidNumber {
+        value: value.to_string(),
+        line: index + 1,
+    })
+}
+
+fn parse_number(value: &str, index: usize, hex: bool) -> Result<i64, ParseError> {
+    let normalised = value.replace('−', "-");
+    let (sign, digits) = match normalised.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", normalised.as_str()),
+    };
+    let parsed = match digits.strip_prefix("0x") {
+        Some(digits) if hex => i64::from_str_radix(&format!("{}{}", sign, digits), 16),
+        _ => normalised.parse::<i64>(),
+    };
+    parsed.map_err(|_| ParseError::InvalidNumber {
+        value: value.to_string(),
+        line: index + 1,
+    })
+}
+
+fn parse_range(
+    caps: &Captures,
+    range: &str,
+    index: usize,
+    hex: bool,
+) -> Result<RangeInclusive<i64>, ParseError> {
+    let low = parse_number(&caps[format!("{}_low", range).as_str()], index, hex)?;
+    let high_value = &caps[format!("{}_high", range).as_str()];
+    let mut high = parse_number(high_value, index, hex)?;
+    let separator = caps.name(&format!("{}_sep", range));
+    if separator.map(|sep| sep.as_str()) == Some("..") {
+        high = high
+            .checked_sub(1)
+            .ok_or_else(|| ParseError::InvalidNumber {
+                value: high_value.to_string(),
+                line: index + 1,
+            })?;
+    }
+    Ok(low..=high)
+}
+
+/// The parts of a single rule line before they are built into a `Rule`. Each
+/// range is held as its inclusive bounds, and a rule written with one range has
+/// only one entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedRuleLine {
+    pub name: String,
+    pub ranges: Vec<(i64, i64)>,
+}
+
+/// Parses one rule line, the `index`th of the input counting from zero, into
+/// its parts using `patterns.rule`. A line which is not a rule is reported as
+/// an unexpected line.
+pub fn parse_rule_line(
+    line: &str,
+    index: usize,
+    patterns: &Patterns,
+) -> Result<ParsedRuleLine, ParseError> {
+    let rule_caps = patterns
+        .rule
+        .captures(line)
+        .ok_or_else(|| ParseError::UnexpectedLine {
+            text: line.to_string(),
+            line: index + 1,
+        })?;
+    let mut ranges = Vec::new();
+    for range in &["range_1", "range_2"] {
+        if rule_caps.name(&format!("{}_low", range)).is_some() {
+            let bounds = parse_range(&rule_caps, range, index, patterns.hex)?;
+            ranges.push((*bounds.start(), *bounds.end()));
+        }
+    }
+    Ok(ParsedRuleLine {
+        name: rule_caps["name"].to_string(),
+        ranges,
+    })
+}
+
+/// Parses a block of rules on its own, one per line, such as the first section
+/// of the input. Parsing stops at the first line which is not a rule.
+pub fn parse_rules(input: &str) -> Result<Vec<Rule>, ParseError> {
+    read_rules(&mut input.lines().enumerate())
+}
+
+pub fn read_rules<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<Vec<Rule>, ParseError> {
+    read_rules_with(lines, &Patterns::default())
+}
+
+/// As `read_rules`, recognising rules with `patterns.rule`.
+pub fn read_rules_with<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    patterns: &Patterns,
+) -> Result<Vec<Rule>, ParseError> {
+    let mut rules: Vec<Rule> = Vec::new();
+    // Rules may instead be packed onto each line separated by semicolons, which
+    // is recognised by the first line holding more than one range.
+    let mut packed = None;
+    for (index, line) in lines {
+        let packed = *packed.get_or_insert_with(|| PACKED_RANGE_REGEX.find_iter(line).count() > 1);
+        let segments: Vec<&str> = if packed {
+            line.split(';')
+                .map(str::trim)
+                .filter(|segment| !segment.is_empty())
+                .collect()
+        } else {
+            vec![line]
+        };
+        for segment in segments {
+            if let Some(rule_caps) = patterns.rule.captures(segment) {
+                let range1 = parse_range(&rule_caps, "range_1", index, patterns.hex)?;
+                // A rule with a single range uses it for both.
+                let range2 = match rule_caps.name("range_2_low") {
+                    Some(_) => parse_range(&rule_caps, "range_2", index, patterns.hex)?,
+                    None => range1.clone(),
+                };
+                let mut rule = Rule::new(rule_caps["name"].to_string(), range1, range2);
+                if let Some(weight) = rule_caps.name("weight") {
+                    rule = rule.with_weight(parse_number(weight.as_str(), index, patterns.hex)?);
+                }
+                rules.push(rule)
+            } else {
+                return Ok(rules);
+            }
+        }
+    }
+    Ok(rules)
+}
+
+pub fn read_passports<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<Vec<Vec<i64>>, ParseError> {
+    read_passports_with(lines, &Patterns::default())
+}
+
+/// As `read_passports`, recognising ticket lines with `patterns.field`.
+pub fn read_passports_with<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    patterns: &Patterns,
+) -> Result<Vec<Vec<i64>>, ParseError> {
+    let mut passports = Vec::new();
+    lines.next();
+    for (index, line) in lines {
+        if is_end_marker(line) {
+            break;
+        }
+        if patterns.field.is_match(line) {
+            passports.push(parse_passport(line, index, patterns.hex)?)
+        } else {
+            break;
+        }
+    }
+    Ok(passports)
+}
+
+// Joins each run of ticket lines into a single ticket, with blank lines
+// between tickets. Tickets are numbered by the line they start on.
+fn read_wrapped_passports(
+    section: &[(usize, &str)],
+    patterns: &Patterns,
+) -> Result<Vec<(usize, Vec<i64>)>, ParseError> {
+    let mut passports = Vec::new();
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    for (index, line) in section.iter().skip(1) {
+        let blank = line.trim().is_empty();
+        if !blank && !patterns.field.is_match(line) {
+            break;
+        }
+        if blank {
+            if let Some((start, pieces)) = current.take() {
+                passports.push((
+                    start,
+                    parse_passport(&pieces.join(","), start, patterns.hex)?,
+                ));
+            }
+        } else {
+            let pieces = &mut current.get_or_insert((*index, Vec::new())).1;
+            pieces.push(line.trim().trim_end_matches(','));
+        }
+    }
+    if let Some((start, pieces)) = current {
+        passports.push((
+            start,
+            parse_passport(&pieces.join(","), start, patterns.hex)?,
+        ));
+    }
+    Ok(passports)
+}
+
+// Trailing empty fields, as left by a trailing comma, are ignored but empty
+// fields between values are an error.
+fn parse_passport(line: &str, index: usize, hex: bool) -> Result<Vec<i64>, ParseError> {
+    let mut fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+    while fields.last() == Some(&"") {
+        fields.pop();
+    }
+    fields
+        .into_iter()
+        .map(|field| {
+            if field.is_empty() {
+                Err(ParseError::EmptyField { line: index + 1 })
+            } else {
+                parse_number(field, index, hex)
+            }
+        })
+        .collect()
+}
+
+/// The values no rule accepts. This tries each rule in turn, which is cheaper
+/// for a single ticket than building `combined_coverage`; the functions
+/// checking many tickets build the coverage once instead.
+pub fn find_invalid_fields<'a>(passport: &'a [i64], rules: &[Rule]) -> Vec<&'a i64> {
+    find_invalid_fields_ignoring(passport, rules, &[])
+}
+
+/// As `find_invalid_fields`, treating the `poison` values as missing rather
+/// than invalid.
+pub fn find_invalid_fields_ignoring<'a>(
+    passport: &'a [i64],
+    rules: &[Rule],
+    poison: &[i64],
+) -> Vec<&'a i64> {
+    let fields = passport
+        .iter()
+        .filter(|field| !poison.contains(field) && rules.iter().all(|rule| !rule.valid(field)))
+        .collect();
+    fields
+}
+
+// As `find_invalid_fields_ignoring`, with the rules already combined into
+// their `combined_coverage`.
+fn invalid_fields_outside<'a>(
+    passport: &'a [i64],
+    coverage: &RangeSet,
+    poison: &[i64],
+) -> Vec<&'a i64> {
+    passport
+        .iter()
+        .filter(|field| !poison.contains(field) && !coverage.contains(field))
+        .collect()
+}
+
+pub fn find_all_invalid_fields<'a>(passports: &'a [Vec<i64>], rules: &[Rule]) -> Vec<&'a i64> {
+    let coverage = combined_coverage(rules);
+    passports
+        .iter()
+        .flat_map(|passport| invalid_fields_outside(passport, &coverage, &[]))
+        .collect()
+}
+
+/// How often each value appears in a column across the tickets. Tickets too
+/// short to have the column are skipped.
+pub fn column_value_histogram(passports: &[Vec<i64>], column: usize) -> BTreeMap<i64, usize> {
+    let mut histogram = BTreeMap::new();
+    for field in passports.iter().filter_map(|passport| passport.get(column)) {
+        *histogram.entry(*field).or_insert(0) += 1;
+    }
+    histogram
+}
+
+/// How many distinct values each column takes across the tickets. A column
+/// with few distinct values rules out few rules.
+pub fn distinct_values_per_column(passports: &[Vec<i64>]) -> Vec<usize> {
+    let columns = passports.iter().map(Vec::len).max().unwrap_or(0);
+    (0..columns)
+        .map(|column| {
+            passports
+                .iter()
+                .filter_map(|passport| passport.get(column))
+                .collect::<HashSet<_>>()
+                .len()
+        })
+        .collect()
+}
+
+/// The ticket and column of the first occurrence of `value`, for tracing where
+/// a suspicious value came from.
+pub fn find_ticket_with_value(passports: &[Vec<i64>], value: i64) -> Option<(usize, usize)> {
+    passports.iter().enumerate().find_map(|(ticket, passport)| {
+        passport
+            .iter()
+            .position(|field| *field == value)
+            .map(|column| (ticket, column))
+    })
+}
+
+/// For each column, the number of nearby tickets whose value there is accepted
+/// by no rule at all.
+pub fn invalid_counts_by_column(passports: &[Vec<i64>], rules: &[Rule]) -> Vec<usize> {
+    let columns = passports.iter().map(Vec::len).max().unwrap_or(0);
+    let mut counts = vec![0; columns];
+    for passport in passports {
+        for (column, field) in passport.iter().enumerate() {
+            if rules.iter().all(|rule| !rule.valid(field)) {
+                counts[column] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Reads ticket lines one at a time, returning false as soon as a value no
+/// rule accepts is seen without reading the rest. Lines without numbers, such
+/// as blanks and the `nearby tickets:` header, are skipped.
+pub fn all_tickets_valid(rules: &[Rule], reader: impl BufRead) -> io::Result<bool> {
+    let coverage = combined_coverage(rules);
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if !FIELD_REGEX.is_match(&line) {
+            continue;
+        }
+        let passport = parse_passport(&line, index, false)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if !invalid_fields_outside(&passport, &coverage, &[]).is_empty() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The distinct rule names in sorted order.
+pub fn rule_names_sorted(rules: &[Rule]) -> Vec<String> {
+    let mut names: Vec<String> = rules.iter().map(|rule| rule.name.clone()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// The values between the lowest and highest bound of any rule which no rule
+/// accepts. A ticket value in one of these gaps is always invalid.
+pub fn coverage_gaps(rules: &[Rule]) -> Vec<RangeInclusive<i64>> {
+    combined_coverage(rules).gaps()
+}
+
+/// Every value at least one rule accepts. A value outside this set is invalid
+/// for part one, which one lookup here decides without trying each rule.
+pub fn combined_coverage(rules: &[Rule]) -> RangeSet {
+    rules
+        .iter()
+        .fold(RangeSet::new(), |set, rule| set.union(&rule.range_set()))
+}
+
+/// Groups the names of rules accepting exactly the same values, such as
+/// `a: 1-3 or 4-5` and `b: 1-5`. Such rules can never be told apart, so any
+/// group here makes the solve ambiguous. Rules with no equivalent are omitted.
+pub fn find_equivalent_rules(rules: &[Rule]) -> Vec<Vec<String>> {
+    let mut groups: Vec<(RangeSet, Vec<String>)> = Vec::new();
+    for rule in rules {
+        let set = rule.range_set();
+        match groups.iter_mut().find(|(existing, _)| *existing == set) {
+            Some((_, names)) => names.push(rule.name.clone()),
+            None => groups.push((set, vec![rule.name.clone()])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, names)| names)
+        .filter(|names| names.len() > 1)
+        .collect()
+}
+
+/// Whether every field on the passport is accepted by at least one of the
+/// given rules, for checking against a subset such as the departure rules.
+pub fn is_valid_against(passport: &[i64], rules: &[&Rule]) -> bool {
+    passport
+        .iter()
+        .all(|field| rules.iter().any(|rule| rule.valid(field)))
+}
+
+/// The column and value of every field on a passport which no rule accepts.
+pub fn explain_rejection(passport: &[i64], rules: &[Rule]) -> Vec<(usize, i64)> {
+    passport
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| rules.iter().all(|rule| !rule.valid(field)))
+        .map(|(column, field)| (column, *field))
+        .collect()
+}
+
+/// Every rule which rejects the value. A value is invalid for part one exactly
+/// when this is all of the rules.
+pub fn rejecting_rules(field: i64, rules: &[Rule]) -> Vec<&Rule> {
+    rules.iter().filter(|rule| !rule.valid(&field)).collect()
+}
+
+/// How many rules accept each value of a ticket, in column order. A count of
+/// zero marks an invalid value and a count near the number of rules one which
+/// constrains little.
+pub fn match_counts(ticket: &[i64], rules: &[Rule]) -> Vec<usize> {
+    ticket
+        .iter()
+        .map(|field| rules.iter().filter(|rule| rule.valid(field)).count())
+        .collect()
+}
+
+/// The rule range lying closest to a value, for suggesting what an invalid
+/// value was meant to be.
+pub fn nearest_range(field: i64, rules: &[Rule]) -> Option<(&Rule, &RangeInclusive<i64>)> {
+    rules
+        .iter()
+        .flat_map(|rule| vec![(rule, &rule.range1), (rule, &rule.range2)])
+        .filter(|(_, range)| !range.is_empty())
+        .min_by_key(|(_, range)| {
+            if field < *range.start() {
+                *range.start() as i128 - field as i128
+            } else {
+                (field as i128 - *range.end() as i128).max(0)
+            }
+        })
+}
+
+/// The distinct values, across all passports, which no rule accepts.
+pub fn distinct_invalid_values(passports: &[Vec<i64>], rules: &[Rule]) -> BTreeSet<i64> {
+    find_all_invalid_fields(passports, rules)
+        .into_iter()
+        .copied()
+        .collect()
+}
+
+pub fn filter_invalid(passports: Vec<Vec<i64>>, rules: &[Rule]) -> Vec<Vec<i64>> {
+    filter_invalid_ignoring(passports, rules, &[])
+}
+
+/// As `filter_invalid`, keeping tickets whose only bad values are `poison`.
+pub fn filter_invalid_ignoring(
+    passports: Vec<Vec<i64>>,
+    rules: &[Rule],
+    poison: &[i64],
+) -> Vec<Vec<i64>> {
+    let coverage = combined_coverage(rules);
+    passports
+        .into_iter()
+        .filter(|passport| invalid_fields_outside(passport, &coverage, poison).is_empty())
+        .collect()
+}
+
+fn is_valid_in_position(rule: &Rule, position: usize, passports: &[Vec<i64>]) -> bool {
+    passports
+        .iter()
+        .all(|passport| rule.valid(&passport[position]))
+}
+
+/// The most candidate positions `solve` will pass on to a solver before giving
+/// up, far beyond the 400 possible for a 20 field puzzle. `solve_with_limit`
+/// takes another limit.
+pub const DEFAULT_MAX_CANDIDATES: usize = 1_000_000;
+
+/// The columns each rule accepts on every ticket, among the first
+/// `num_columns` of each ticket.
+pub fn find_all_valid_positions(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+) -> Vec<Vec<(usize, String)>> {
+    find_all_valid_positions_limited(rules, passports, num_columns, usize::MAX)
+        .expect("an unlimited search cannot exceed its limit")
+}
+
+/// As `find_all_valid_positions`, but fails when more than `max_candidates`
+/// positions survive in total, protecting the solvers from inputs where nearly
+/// every rule fits nearly every column. The limit is checked once the tickets
+/// have been scanned, not while collecting: the scan starts from every rule and
+/// column pair and only removes them, so its cost is fixed by the input size
+/// whatever the limit.
+pub fn find_all_valid_positions_limited(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+    max_candidates: usize,
+) -> Result<Vec<Vec<(usize, String)>>, SolveError> {
+    valid_positions_ignoring(rules, passports, num_columns, &[], max_candidates)
+}
+
+// Collects the candidates, where a `poison` value rules nothing out. Each
+// ticket only re-checks the pairs no earlier ticket has ruled out, and the scan
+// stops once no rule has a column left.
+fn valid_positions_ignoring(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+    poison: &[i64],
+    max_candidates: usize,
+) -> Result<Vec<Vec<(usize, String)>>, SolveError> {
+    let columns: Vec<usize> = (0..num_columns).collect();
+    let mut candidates = vec![columns; rules.len()];
+    for passport in passports {
+        if candidates.iter().all(Vec::is_empty) {
+            break;
+        }
+        for (rule, columns) in rules.iter().zip(candidates.iter_mut()) {
+            columns.retain(|column| {
+                let field = &passport[*column];
+                poison.contains(field) || rule.valid(field)
+            });
+        }
+    }
+    if candidates.iter().map(Vec::len).sum::<usize>() > max_candidates {
+        return Err(SolveError::TooManyCandidates {
+            limit: max_candidates,
+        });
+    }
+    Ok(rules
+        .iter()
+        .zip(candidates)
+        .map(|(rule, columns)| {
+            columns
+                .into_iter()
+                .map(|column| (column, rule.name.clone()))
+                .collect()
+        })
+        .collect())
+}
+
+/// For every (rule, column) pair ruled out, the index of the first ticket with
+/// a value there that the rule rejects. Pairs which survive are absent.
+pub fn elimination_witnesses(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+) -> HashMap<(usize, usize), usize> {
+    let mut witnesses = HashMap::new();
+    for (rule_index, rule) in rules.iter().enumerate() {
+        for column in 0..num_columns {
+            if let Some(ticket) = passports
+                .iter()
+                .position(|passport| !rule.valid(&passport[column]))
+            {
+                witnesses.insert((rule_index, column), ticket);
+            }
+        }
+    }
+    witnesses
+}
+
+/// The columns every rule accepts on every ticket. These rule nothing out, so
+/// many of them leave the input under-determined.
+pub fn unconstrained_columns(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+) -> Vec<usize> {
+    (0..num_columns)
+        .filter(|column| {
+            rules
+                .iter()
+                .all(|rule| is_valid_in_position(rule, *column, passports))
+        })
+        .collect()
+}
+
+/// The tickets whose every value every rule accepts. These rule out nothing at
+/// all, a stronger condition than being redundant, so they can always be
+/// dropped when cutting an input down.
+pub fn non_constraining_tickets(rules: &[Rule], valid_tickets: &[Vec<i64>]) -> Vec<usize> {
+    valid_tickets
+        .iter()
+        .enumerate()
+        .filter(|(_, ticket)| {
+            ticket
+                .iter()
+                .all(|field| rules.iter().all(|rule| rule.valid(field)))
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// The tickets which could each be dropped without changing any rule's
+/// candidate columns, because every pair they rule out is also ruled out by
+/// another ticket. Two copies of a ticket are both listed, though only one of
+/// them may go.
+pub fn redundant_tickets(rules: &[Rule], passports: &[Vec<i64>], num_columns: usize) -> Vec<usize> {
+    let mut needed = vec![false; passports.len()];
+    for rule in rules {
+        for column in 0..num_columns {
+            let mut rejecting = passports
+                .iter()
+                .enumerate()
+                .filter(|(_, passport)| !rule.valid(&passport[column]))
+                .map(|(ticket, _)| ticket);
+            if let (Some(ticket), None) = (rejecting.next(), rejecting.next()) {
+                needed[ticket] = true;
+            }
+        }
+    }
+    (0..passports.len())
+        .filter(|ticket| !needed[*ticket])
+        .collect()
+}
+
+/// `grid[rule][column]` is true when every passport's value in that column is
+/// accepted by the rule.
+pub fn feasibility_grid(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+) -> Vec<Vec<bool>> {
+    rules
+        .iter()
+        .map(|rule| {
+            (0..num_columns)
+                .map(|position| is_valid_in_position(rule, position, passports))
+                .collect()
+        })
+        .collect()
+}
+
+/// The column accepted by the fewest rules and how many rules accept it, a
+/// good pivot for a backtracking search. Ties go to the lowest column.
+pub fn tightest_column(
+    rules: &[Rule],
+    passports: &[Vec<i64>],
+    num_columns: usize,
+) -> Option<(usize, usize)> {
+    let grid = feasibility_grid(rules, passports, num_columns);
+    (0..num_columns)
+        .map(|column| (column, grid.iter().filter(|row| row[column]).count()))
+        .min_by_key(|(column, count)| (*count, *column))
+}
+
+/// The names of the rules which fit no column. The candidate lists for these
+/// are empty so the names come from `rules`, which must be in the same order
+/// as `valid_positions`.
+pub fn unplaceable_rules(rules: &[Rule], valid_positions: &[Vec<(usize, String)>]) -> Vec<String> {
+    rules
+        .iter()
+        .zip(valid_positions)
+        .filter(|(_, positions)| positions.is_empty())
+        .map(|(rule, _)| rule.name.clone())
+        .collect()
+}
+
+/// The names of the rules which fit every one of the `num_columns` columns.
+/// These rule nothing out, so they are the first place to look when a solve is
+/// ambiguous.
+pub fn fully_ambiguous_rules(
+    valid_positions: &[Vec<(usize, String)>],
+    num_columns: usize,
+) -> Vec<String> {
+    valid_positions
+        .iter()
+        .filter(|positions| {
+            let columns: HashSet<usize> = positions
+                .iter()
+                .map(|(position, _)| *position)
+                .filter(|position| *position < num_columns)
+                .collect();
+            !positions.is_empty() && columns.len() == num_columns
+        })
+        .map(|positions| positions[0].1.clone())
+        .collect()
+}
+
+fn candidate_columns(all_positions: &[Vec<(usize, String)>]) -> Vec<Vec<usize>> {
+    all_positions
+        .iter()
+        .map(|positions| positions.iter().map(|(position, _)| *position).collect())
+        .collect()
+}
+
+/// The sorted columns the rule at `rule_index` may occupy.
+pub fn feasible_columns(rule_index: usize, valid_positions: &[Vec<(usize, String)>]) -> Vec<usize> {
+    let mut columns: Vec<usize> = valid_positions[rule_index]
+        .iter()
+        .map(|(position, _)| *position)
+        .collect();
+    columns.sort_unstable();
+    columns
+}
+
+/// The fraction of the n² (rule, column) pairs which are still candidates. A
+/// density near 1.0 means almost any rule fits almost any column.
+pub fn candidate_density(valid_positions: &[Vec<(usize, String)>]) -> f64 {
+    let rules = valid_positions.len();
+    if rules == 0 {
+        return 0.0;
+    }
+    let feasible: usize = valid_positions.iter().map(Vec::len).sum();
+    feasible as f64 / (rules * rules) as f64
+}
+
+/// Every rule's candidate count in ascending order. Inputs built to be solved
+/// by elimination give the chain 1, 2, 3, ... n.
+pub fn candidate_size_histogram(valid_positions: &[Vec<(usize, String)>]) -> Vec<usize> {
+    let mut sizes: Vec<usize> = valid_positions.iter().map(Vec::len).collect();
+    sizes.sort_unstable();
+    sizes
+}
+
+/// The product of every rule's candidate count, the size of the search space
+/// before any two rules are stopped from sharing a column. Saturates rather
+/// than overflowing.
+pub fn search_space_estimate(valid_positions: &[Vec<(usize, String)>]) -> u128 {
+    valid_positions.iter().fold(1u128, |product, positions| {
+        product.saturating_mul(positions.len() as u128)
+    })
+}
+
+/// Whether exactly one assignment of rules to columns is possible.
+pub fn is_unique_solution(valid_positions: &[Vec<(usize, String)>]) -> bool {
+    all_assignments(&candidate_columns(valid_positions), 2).len() == 1
+}
+
+/// Picks the lexicographically smallest assignment, taking rules in order and
+/// each rule's lowest possible column, rather than failing when there is more
+/// than one. The flag is true only when the assignment is the unique one. With
+/// no possible assignment the result is empty and not unique.
+pub fn solve_best_effort(valid_positions: &[Vec<(usize, String)>]) -> (Vec<(usize, String)>, bool) {
+    let mut candidates = candidate_columns(valid_positions);
+    for columns in &mut candidates {
+        columns.sort_unstable();
+    }
+    let assignments = all_assignments(&candidates, 2);
+    match assignments.first() {
+        Some(assignment) => (
+            assignment
+                .iter()
+                .zip(valid_positions)
+                .map(|(column, positions)| (*column, positions[0].1.clone()))
+                .collect(),
+            assignments.len() == 1,
+        ),
+        None => (Vec::new(), false),
+    }
+}
+
+pub fn determine_field_positions(
+    all_positions: Vec<Vec<(usize, String)>>,
+) -> Result<Vec<(usize, String)>, SolveError> {
+    let candidates = candidate_columns(&all_positions);
+    let assignment = GreedyElimination.solve(&candidates)?;
+
+    let mut determined_positions: Vec<(usize, (usize, String))> = all_positions
+        .into_iter()
+        .zip(assignment)
+        .map(|(positions, position)| (positions.len(), (position, positions[0].1.clone())))
+        .collect();
+    determined_positions.sort_by_key(|(count, _)| *count);
+    Ok(determined_positions
+        .into_iter()
+        .map(|(_, determined)| determined)
+        .collect())
+}
+
+/// Solves with some field to column assignments already known. Hinted fields
+/// are fixed to their columns, which are removed from every other field before
+/// solving the rest.
+pub fn solve_with_hints(
+    valid_positions: &[Vec<(usize, String)>],
+    hints: &[(String, usize)],
+) -> Result<Vec<(usize, String)>, SolveError> {
+    let mut hinted_columns = HashSet::new();
+    let mut hinted_names = HashSet::new();
+    for (name, column) in hints {
+        if !valid_positions
+            .iter()
+            .any(|positions| positions.iter().any(|(_, rule)| rule == name))
+        {
+            return Err(SolveError::UnknownField(name.clone()));
+        }
+        if !hinted_columns.insert(*column) || !hinted_names.insert(name) {
+            return Err(SolveError::ConflictingHint(name.clone()));
+        }
+    }
+
+    let mut narrowed = Vec::new();
+    for positions in valid_positions {
+        let hint = positions
+            .first()
+            .and_then(|(_, rule)| hints.iter().find(|(name, _)| name == rule));
+        narrowed.push(match hint {
+            Some((name, column)) => {
+                let pinned: Vec<(usize, String)> = positions
+                    .iter()
+                    .filter(|(position, _)| position == column)
+                    .cloned()
+                    .collect();
+                if pinned.is_empty() {
+                    return Err(SolveError::ConflictingHint(name.clone()));
+                }
+                pinned
+            }
+            None => positions
+                .iter()
+                .filter(|(position, _)| !hinted_columns.contains(position))
+                .cloned()
+                .collect(),
+        });
+    }
+    determine_field_positions(narrowed)
+}
+
+/// What elimination alone could work out: the fields fixed to a column, in
+/// column order, and every other rule with the columns still open to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PartialSolution {
+    pub determined: Vec<(usize, String)>,
+    pub ambiguous: Vec<(String, Vec<usize>)>,
+}
+
+impl PartialSolution {
+    pub fn is_complete(&self) -> bool {
+        self.ambiguous.is_empty()
+    }
+}
+
+/// Fixes every rule forced to a single free column, as `GreedyElimination`
+/// does, but returns what it found instead of failing once no rule is forced.
+/// Rules with no candidates at all are left out, see `unplaceable_rules`.
+pub fn solve_partial(valid_positions: &[Vec<(usize, String)>]) -> PartialSolution {
+    let candidates = candidate_columns(valid_positions);
+    let mut assigned: Vec<Option<usize>> = vec![None; candidates.len()];
+    let mut taken = HashSet::new();
+    loop {
+        let forced = candidates.iter().enumerate().find_map(|(rule, columns)| {
+            let mut free = columns.iter().filter(|column| !taken.contains(*column));
+            match (assigned[rule], free.next(), free.next()) {
+                (None, Some(column), None) => Some((rule, *column)),
+                _ => None,
+            }
+        });
+        match forced {
+            Some((rule, column)) => {
+                assigned[rule] = Some(column);
+                taken.insert(column);
+            }
+            None => break,
+        }
+    }
+
+    let mut solution = PartialSolution::default();
+    for ((rule, columns), column) in valid_positions.iter().zip(&candidates).zip(assigned) {
+        let name = match rule.first() {
+            Some((_, name)) => name.clone(),
+            None => continue,
+        };
+        match column {
+            Some(column) => solution.determined.push((column, name)),
+            None => {
+                let mut free: Vec<usize> = columns
+                    .iter()
+                    .copied()
+                    .filter(|column| !taken.contains(column))
+                    .collect();
+                free.sort_unstable();
+                solution.ambiguous.push((name, free));
+            }
+        }
+    }
+    solution.determined.sort_unstable();
+    solution
+}
+
+/// The columns and rule names not yet in `partial_assignment`, each in
+/// ascending order, for stepping through a solve by hand. Rules with no
+/// candidates cannot be named from `valid_positions` and are left out.
+pub fn remaining_state(
+    partial_assignment: &[(usize, String)],
+    valid_positions: &[Vec<(usize, String)>],
+) -> (Vec<usize>, Vec<String>) {
+    let columns = (0..valid_positions.len())
+        .filter(|column| {
+            partial_assignment
+                .iter()
+                .all(|(assigned, _)| assigned != column)
+        })
+        .collect();
+    let mut names: Vec<String> = valid_positions
+        .iter()
+        .filter_map(|positions| positions.first())
+        .map(|(_, name)| name.clone())
+        .filter(|name| {
+            partial_assignment
+                .iter()
+                .all(|(_, assigned)| assigned != name)
+        })
+        .collect();
+    names.sort_unstable();
+    (columns, names)
+}
+
+/// Runs the whole part two pipeline, discarding invalid nearby tickets and
+/// assigning every rule to a column.
+pub fn solve(notes: &Notes) -> Result<Vec<(usize, String)>, SolveError> {
+    solve_ignoring(notes, &[])
+}
+
+/// As `solve`, treating the `poison` values as missing: they neither make a
+/// ticket invalid nor rule out a column.
+pub fn solve_ignoring(notes: &Notes, poison: &[i64]) -> Result<Vec<(usize, String)>, SolveError> {
+    solve_limited(notes, poison, DEFAULT_MAX_CANDIDATES)
+}
+
+/// As `solve`, failing with `SolveError::TooManyCandidates` when more than
+/// `max_candidates` positions are left for the solver rather than more than
+/// `DEFAULT_MAX_CANDIDATES`.
+pub fn solve_with_limit(
+    notes: &Notes,
+    max_candidates: usize,
+) -> Result<Vec<(usize, String)>, SolveError> {
+    solve_limited(notes, &[], max_candidates)
+}
+
+fn solve_limited(
+    notes: &Notes,
+    poison: &[i64],
+    max_candidates: usize,
+) -> Result<Vec<(usize, String)>, SolveError> {
+    check_ticket_widths(notes)?;
+    let valid_passports =
+        filter_invalid_ignoring(notes.near_passports.clone(), &notes.rules, poison);
+    let valid_positions = valid_positions_ignoring(
+        &notes.rules,
+        &valid_passports,
+        notes.num_columns(),
+        poison,
+        max_candidates,
+    )?;
+    determine_field_positions(valid_positions)
+}
+
+// Fails when I have no ticket, or on the first ticket without `num_columns`
+// fields. The candidate search and the answers index tickets by column, so
+// would otherwise panic or ignore part of the ticket.
+fn check_ticket_widths(notes: &Notes) -> Result<(), SolveError> {
+    if notes.my_passports.is_empty() {
+        return Err(SolveError::NoTicket);
+    }
+    let expected = notes.num_columns();
+    if let Some(ticket) = notes
+        .my_passports
+        .iter()
+        .position(|passport| passport.len() != expected)
+    {
+        return Err(SolveError::MyTicketLength {
+            ticket,
+            expected,
+            found: notes.my_passports[ticket].len(),
+        });
+    }
+    match notes
+        .near_passports
+        .iter()
+        .position(|passport| passport.len() != expected)
+    {
+        Some(ticket) => Err(SolveError::TicketLength {
+            ticket,
+            expected,
+            found: notes.near_passports[ticket].len(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// As `solve`, but instead of discarding a ticket with an invalid value it
+/// ignores just that value, so the ticket's other columns still rule rules
+/// out. This generally leaves fewer candidates than the puzzle's method, and
+/// can solve inputs which are otherwise ambiguous.
+pub fn solve_masked(notes: &Notes) -> Result<Vec<(usize, String)>, SolveError> {
+    let invalid: Vec<i64> = distinct_invalid_values(&notes.near_passports, &notes.rules)
+        .into_iter()
+        .collect();
+    solve_ignoring(notes, &invalid)
+}
+
+/// Indices into `notes.near_passports` of a small set of valid tickets which
+/// still solve uniquely, for cutting an input down to a reproducer. Tickets are
+/// dropped greedily in order, so the set is minimal but not necessarily the
+/// smallest.
+pub fn minimal_ticket_subset(notes: &Notes) -> Result<Vec<usize>, SolveError> {
+    solve(notes)?;
+    let coverage = combined_coverage(&notes.rules);
+    let mut kept: Vec<usize> = (0..notes.near_passports.len())
+        .filter(|ticket| {
+            invalid_fields_outside(&notes.near_passports[*ticket], &coverage, &[]).is_empty()
+        })
+        .collect();
+    let mut ticket = 0;
+    while ticket < kept.len() {
+        let without: Vec<Vec<i64>> = kept
+            .iter()
+            .filter(|index| **index != kept[ticket])
+            .map(|index| notes.near_passports[*index].clone())
+            .collect();
+        if is_unique_solution(&find_all_valid_positions(
+            &notes.rules,
+            &without,
+            notes.num_columns(),
+        )) {
+            kept.remove(ticket);
+        } else {
+            ticket += 1;
+        }
+    }
+    Ok(kept)
+}
+
+/// Solves the notes and returns the field name for each column, in column
+/// order.
+pub fn solve_to_columns(notes: &Notes) -> Result<Vec<String>, SolveError> {
+    let mut determined_positions = solve(notes)?;
+    determined_positions.sort_unstable();
+    Ok(determined_positions
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect())
+}
+
+/// Solves the notes and returns the column of each rule, indexed in the order
+/// the rules were given.
+pub fn solve_permutation(notes: &Notes) -> Result<Vec<usize>, SolveError> {
+    let columns: HashMap<String, usize> = solve(notes)?
+        .into_iter()
+        .map(|(column, name)| (name, column))
+        .collect();
+    Ok(notes.rules.iter().map(|rule| columns[&rule.name]).collect())
+}
+
+/// Solves the notes and compares the result with the expected column for each
+/// field, returning a description of every disagreement. Fields solved but not
+/// expected are reported too, as is a failure to solve at all.
+pub fn assert_solution(notes: &Notes, expected: &[(&str, usize)]) -> Result<(), Vec<String>> {
+    let solved = solve(notes).map_err(|err| vec![err.to_string()])?;
+    let columns: HashMap<&str, usize> = solved
+        .iter()
+        .map(|(column, name)| (name.as_str(), *column))
+        .collect();
+    let mut mismatches: Vec<String> = expected
+        .iter()
+        .filter_map(|(name, column)| match columns.get(name) {
+            Some(found) if found == column => None,
+            Some(found) => Some(format!(
+                "{}: expected column {}, found {}",
+                name, column, found
+            )),
+            None => Some(format!("{}: expected column {}, not solved", name, column)),
+        })
+        .collect();
+    mismatches.extend(
+        solved
+            .iter()
+            .filter(|(_, name)| expected.iter().all(|(other, _)| other != name))
+            .map(|(column, name)| format!("{}: found column {}, not expected", name, column)),
+    );
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+/// Selects the fields whose names start with a prefix, optionally ignoring
+/// case. Only the filtering is affected; rule names are parsed as written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPrefix {
+    pub prefix: String,
+    pub ignore_case: bool,
+}
+
+impl FieldPrefix {
+    pub fn new(prefix: &str) -> FieldPrefix {
+        FieldPrefix {
+            prefix: prefix.to_string(),
+            ignore_case: false,
+        }
+    }
+
+    pub fn ignore_case(mut self) -> FieldPrefix {
+        self.ignore_case = true;
+        self
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        if self.ignore_case {
+            name.to_lowercase().starts_with(&self.prefix.to_lowercase())
+        } else {
+            name.starts_with(&self.prefix)
+        }
+    }
+}
+
+impl Default for FieldPrefix {
+    fn default() -> FieldPrefix {
+        FieldPrefix::new("departure")
+    }
+}
+
+fn product_of_departures(
+    passport: &[i64],
+    determined_positions: &[(usize, String)],
+    prefix: &FieldPrefix,
+) -> i64 {
+    determined_positions
+        .iter()
+        .filter(|(_, name)| prefix.matches(name))
+        .map(|(field, _)| passport[*field])
+        .product()
+}
+
+pub fn departure_product(notes: &Notes) -> Result<i64, SolveError> {
+    prefix_product(notes, &FieldPrefix::default())
+}
+
+/// The product of my ticket's values for the fields selected by `prefix`.
+pub fn prefix_product(notes: &Notes, prefix: &FieldPrefix) -> Result<i64, SolveError> {
+    let determined_positions = solve(notes)?;
+    Ok(product_of_departures(
+        my_ticket(notes)?,
+        &determined_positions,
+        prefix,
+    ))
+}
+
+// My first ticket, for the answers which read values from it.
+fn my_ticket(notes: &Notes) -> Result<&[i64], SolveError> {
+    notes.my_passport().ok_or(SolveError::NoTicket)
+}
+
+/// As `prefix_product`, reduced modulo `modulus` as it goes so that products
+/// too large for an `i64` can still be checked. The result lies in
+/// `0..modulus`; a modulus which is not positive fails with
+/// `SolveError::InvalidModulus`.
+pub fn departure_product_mod(
+    notes: &Notes,
+    prefix: &FieldPrefix,
+    modulus: i64,
+) -> Result<i64, SolveError> {
+    if modulus <= 0 {
+        return Err(SolveError::InvalidModulus(modulus));
+    }
+    let modulus = i128::from(modulus);
+    let determined_positions = solve(notes)?;
+    let passport = my_ticket(notes)?;
+    Ok(determined_positions
+        .iter()
+        .filter(|(_, name)| prefix.matches(name))
+        .fold(1 % modulus, |product, (field, _)| {
+            product * i128::from(passport[*field]).rem_euclid(modulus) % modulus
+        }) as i64)
+}
+
+/// Each departure field and my ticket's value for it, in column order, so that
+/// the part two product can be checked by hand.
+pub fn departure_breakdown(notes: &Notes) -> Result<Vec<(String, i64)>, SolveError> {
+    prefix_breakdown(notes, &FieldPrefix::default())
+}
+
+/// As `departure_breakdown`, for the fields selected by `prefix`.
+pub fn prefix_breakdown(
+    notes: &Notes,
+    prefix: &FieldPrefix,
+) -> Result<Vec<(String, i64)>, SolveError> {
+    assignment_breakdown(notes, &solve(notes)?, prefix)
+}
+
+/// As `prefix_breakdown`, for an assignment already solved from `notes`.
+pub fn assignment_breakdown(
+    notes: &Notes,
+    assignment: &[(usize, String)],
+    prefix: &FieldPrefix,
+) -> Result<Vec<(String, i64)>, SolveError> {
+    let passport = my_ticket(notes)?;
+    let mut determined_positions = assignment.to_vec();
+    determined_positions.sort_unstable();
+    Ok(determined_positions
+        .into_iter()
+        .filter(|(_, name)| prefix.matches(name))
+        .map(|(column, name)| (name, passport[column]))
+        .collect())
+}
+
+/// How `aggregate_fields` combines the selected values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Product,
+    Max,
+    Min,
+}
+
+/// Combines my ticket's values for the fields selected by `prefix`. Part two
+/// is `Aggregate::Product` over the departure fields. The maximum and minimum
+/// of no fields fail with `SolveError::NoMatchingFields`, and a sum or product
+/// too large for an `i64` with `SolveError::Overflow`.
+pub fn aggregate_fields(
+    notes: &Notes,
+    prefix: &FieldPrefix,
+    op: Aggregate,
+) -> Result<i64, SolveError> {
+    let mut values = prefix_breakdown(notes, prefix)?
+        .into_iter()
+        .map(|(_, value)| value);
+    let aggregate = match op {
+        Aggregate::Sum => values
+            .try_fold(0i64, i64::checked_add)
+            .ok_or(SolveError::Overflow)?,
+        Aggregate::Product => values
+            .try_fold(1i64, i64::checked_mul)
+            .ok_or(SolveError::Overflow)?,
+        Aggregate::Max => values
+            .max()
+            .ok_or_else(|| SolveError::NoMatchingFields(prefix.prefix.clone()))?,
+        Aggregate::Min => values
+            .min()
+            .ok_or_else(|| SolveError::NoMatchingFields(prefix.prefix.clone()))?,
+    };
+    Ok(aggregate)
+}
+
+/// The departure product of each personal ticket, sharing a single solve.
+pub fn departure_products(notes: &Notes) -> Result<Vec<i64>, SolveError> {
+    let determined_positions = solve(notes)?;
+    Ok(notes
+        .my_passports
+        .iter()
+        .map(|passport| {
+            product_of_departures(passport, &determined_positions, &FieldPrefix::default())
+        })
+        .collect())
+}
+
+/// Multiplies together the values of the named fields on my ticket, failing
+/// with `SolveError::Overflow` when the product does not fit in an `i64`.
+pub fn product_of_fields(notes: &Notes, names: &[&str]) -> Result<i64, SolveError> {
+    let determined_positions = solve(notes)?;
+    let passport = my_ticket(notes)?;
+    let mut product = 1;
+    for name in names {
+        let (field, _) = determined_positions
+            .iter()
+            .find(|(_, determined)| determined == name)
+            .ok_or_else(|| SolveError::UnknownField(name.to_string()))?;
+        product = passport[*field]
+            .checked_mul(product)
+            .ok_or(SolveError::Overflow)?;
+    }
+    Ok(product)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use indoc::indoc;
+
+    const TEST_INPUT: &str = indoc! {"
+        class: 1-3 or 5-7
+        row: 6-11 or 33-44
+        seat: 13-40 or 45-50
+
+        your ticket:
+        7,1,14
+
+        nearby tickets:
+        7,3,47
+        40,4,50
+        55,2,20
+        38,6,12
+    "};
+
+    const TEST_INPUT_TWO: &str = indoc! {"
+        class: 0-1 or 4-19
+        row: 0-5 or 8-19
+        seat: 0-13 or 16-19
+
+        your ticket:
+        11,12,13
+
+        nearby tickets:
+        3,9,18
+        15,1,5
+        5,14,9
+    "};
+
+    const MARKER_INPUT: &str = indoc! {"
+        [RULES]
+        class: 1-3 or 5-7
+        row: 6-11 or 33-44
+        seat: 13-40 or 45-50
+        [MY]
+        7,1,14
+        [NEARBY]
+        7,3,47
+        40,4,50
+        55,2,20
+        38,6,12
+    "};
+
+    #[test]
+    fn it_collects_correct_invalid_fields() {
+        let mut lines = TEST_INPUT.lines().enumerate();
+        let rules = read_rules(&mut lines).unwrap();
+
+        read_passports(&mut lines).unwrap(); // read my passport
+        let near_passports = read_passports(&mut lines).unwrap();
+        let invalid_fields = find_all_invalid_fields(&near_passports, &rules);
+        assert_eq!(invalid_fields, vec![&4, &55, &12]);
+    }
+
+    #[test]
+    fn it_collects_distinct_invalid_values() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let invalid_values = distinct_invalid_values(&notes.near_passports, &notes.rules);
+        assert_eq!(invalid_values, [4, 12, 55].iter().copied().collect());
+    }
+
+    #[test]
+    fn it_checks_rule_bounds_against_a_ceiling() {
+        let mut notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(notes.assert_bounds(50), Ok(()));
+        assert_eq!(notes.assert_bounds(44), Err(vec!["seat".to_string()]));
+        notes
+            .rules
+            .push(Rule::new("zone".to_string(), 10000..=10001, 1..=2));
+        assert_eq!(notes.assert_bounds(9999), Err(vec!["zone".to_string()]));
+    }
+
+    #[test]
+    fn it_round_trips_the_compact_form() {
+        let compact = "class:1-3or5-7;row:6-11or33-44;seat:13-40or45-50|7,1,14|7,3,47;40,4,50;55,2,20;38,6,12";
+        let notes = Notes::from_compact(compact).unwrap();
+        assert_eq!(notes, Notes::parse(TEST_INPUT).unwrap());
+        assert_eq!(notes.to_compact(), compact);
+
+        let real = Notes::parse(include_str!("../input.txt")).unwrap();
+        assert_eq!(Notes::from_compact(&real.to_compact()), Ok(real));
+        assert!(Notes::from_compact("class:1-3or5-7").is_err());
+
+        let spaced = TEST_INPUT.replace("class:", "floor or wall:");
+        let notes = Notes::parse(&spaced).unwrap();
+        let compact = notes.to_compact();
+        assert!(compact.starts_with("floor or wall:1-3or5-7;"));
+        assert_eq!(Notes::from_compact(&compact), Ok(notes));
+    }
+
+    #[test]
+    fn it_counts_rules_and_columns() {
+        let mut notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(notes.num_rules(), 3);
+        assert_eq!(notes.num_columns(), 3);
+        notes.my_passports.clear();
+        assert_eq!(notes.num_columns(), 3);
+
+        // The candidate search only looks at the columns it is given.
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert_eq!(
+            find_all_valid_positions(&notes.rules, &valid_passports, 2),
+            vec![
+                vec![(1, "class".to_string())],
+                vec![(0, "row".to_string()), (1, "row".to_string())],
+                vec![]
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_my_tickets_of_the_wrong_width() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        notes.my_passports.push(vec![1, 2]);
+        let expected = SolveError::MyTicketLength {
+            ticket: 1,
+            expected: 3,
+            found: 2,
+        };
+        assert_eq!(departure_products(&notes), Err(expected.clone()));
+        assert_eq!(prefix_product(&notes, &FieldPrefix::new("")), Err(expected));
+    }
+
+    #[test]
+    fn it_needs_a_ticket_of_mine() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        notes.my_passports.clear();
+        assert_eq!(notes.my_passport(), None);
+        assert_eq!(solve(&notes), Err(SolveError::NoTicket));
+        assert_eq!(departure_product(&notes), Err(SolveError::NoTicket));
+        assert_eq!(
+            product_of_fields(&notes, &["class"]),
+            Err(SolveError::NoTicket)
+        );
+        assert_eq!(
+            assignment_breakdown(&notes, &assignment, &FieldPrefix::new("")),
+            Err(SolveError::NoTicket)
+        );
+    }
+
+    #[test]
+    fn it_rejects_nearby_tickets_of_the_wrong_width() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        notes.near_passports.insert(1, vec![1, 2]);
+        let expected = Err(SolveError::TicketLength {
+            ticket: 1,
+            expected: 3,
+            found: 2,
+        });
+        assert_eq!(solve(&notes), expected);
+        assert_eq!(
+            solve_timed(&notes).map(|(assignment, _)| assignment),
+            expected
+        );
+        notes.near_passports[1] = vec![1, 2, 3, 4];
+        assert!(solve(&notes).is_err());
+    }
+
+    #[test]
+    fn it_merges_notes_with_the_same_rules() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let mut other = notes.clone();
+        other.my_passports = vec![vec![1, 2, 3]];
+        other.near_passports = vec![vec![19, 19, 19]];
+        let merged = notes.clone().merge(other).unwrap();
+        assert_eq!(merged.my_passports, notes.my_passports);
+        assert_eq!(merged.near_passports.len(), 4);
+        assert_eq!(merged.near_passports[3], vec![19, 19, 19]);
+
+        let mut other = notes.clone();
+        other.rules[1].range2 = 8..=20;
+        assert_eq!(
+            notes.clone().merge(other),
+            Err(MergeError::DifferentRule("row".to_string()))
+        );
+        let mut other = notes.clone();
+        other.rules.pop();
+        assert_eq!(
+            notes.merge(other),
+            Err(MergeError::RuleCount {
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn it_looks_up_rules_by_name() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(
+            notes.rule_by_name("seat"),
+            Some(&Rule::new("seat".to_string(), 0..=13, 16..=19))
+        );
+        assert_eq!(notes.rule_by_name("wagon"), None);
+    }
+
+    #[test]
+    fn it_checks_values_against_the_combined_coverage() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let coverage = combined_coverage(&notes.rules);
+        assert_eq!(coverage.ranges(), &[1..=3, 5..=11, 13..=50]);
+        let by_rule: Vec<&i64> = notes
+            .near_passports
+            .iter()
+            .flat_map(|passport| find_invalid_fields(passport, &notes.rules))
+            .collect();
+        assert_eq!(
+            find_all_invalid_fields(&notes.near_passports, &notes.rules),
+            by_rule
+        );
+    }
+
+    #[test]
+    fn it_lists_the_rules_rejecting_a_value() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let all: Vec<&Rule> = notes.rules.iter().collect();
+        assert_eq!(rejecting_rules(4, &notes.rules), all);
+        assert_eq!(rejecting_rules(7, &notes.rules), vec![&notes.rules[2]]);
+    }
+
+    #[test]
+    fn it_counts_the_rules_matching_each_value() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(match_counts(&[7, 1, 14], &notes.rules), vec![2, 1, 1]);
+        assert_eq!(match_counts(&[4, 55], &notes.rules), vec![0, 0]);
+    }
+
+    #[test]
+    fn it_sorts_rule_names() {
+        let mut notes = Notes::parse(TEST_INPUT).unwrap();
+        notes.rules.reverse();
+        notes.rules.push(notes.rules[0].clone());
+        assert_eq!(
+            rule_names_sorted(&notes.rules),
+            vec!["class", "row", "seat"]
+        );
+    }
+
+    #[test]
+    fn it_counts_the_values_in_a_column() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let histogram = column_value_histogram(&notes.near_passports, 0);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![(3, 1), (5, 1), (15, 1)]
+        );
+        notes.near_passports.push(vec![5]);
+        assert_eq!(column_value_histogram(&notes.near_passports, 0)[&5], 2);
+        assert!(column_value_histogram(&notes.near_passports, 3).is_empty());
+    }
+
+    #[test]
+    fn it_counts_distinct_values_per_column() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(
+            distinct_values_per_column(&notes.near_passports),
+            vec![3, 3, 3]
+        );
+        notes.near_passports.push(vec![3, 1, 7]);
+        assert_eq!(
+            distinct_values_per_column(&notes.near_passports),
+            vec![3, 3, 4]
+        );
+        assert!(distinct_values_per_column(&[]).is_empty());
+    }
+
+    #[test]
+    fn it_finds_the_first_ticket_with_a_value() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(
+            find_ticket_with_value(&notes.near_passports, 55),
+            Some((2, 0))
+        );
+        assert_eq!(
+            find_ticket_with_value(&notes.near_passports, 50),
+            Some((1, 2))
+        );
+        assert_eq!(find_ticket_with_value(&notes.near_passports, 99), None);
+    }
+
+    #[test]
+    fn it_counts_invalid_values_by_column() {
+        let mut notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(
+            invalid_counts_by_column(&notes.near_passports, &notes.rules),
+            vec![1, 1, 1]
+        );
+        notes.near_passports.push(vec![0, 4, 51]);
+        assert_eq!(
+            invalid_counts_by_column(&notes.near_passports, &notes.rules),
+            vec![2, 2, 2]
+        );
+        notes.near_passports.push(vec![99, 1, 1]);
+        assert_eq!(
+            invalid_counts_by_column(&notes.near_passports, &notes.rules),
+            vec![3, 2, 2]
+        );
+    }
+
+    // Fails any read, to show that nothing past an invalid ticket is read.
+    struct Unreadable;
+
+    impl Read for Unreadable {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("read past invalid ticket"))
+        }
+    }
+
+    #[test]
+    fn it_streams_ticket_validation() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let clean = "nearby tickets:\n7,3,47\n\n3,7,13\n";
+        assert!(all_tickets_valid(&notes.rules, clean.as_bytes()).unwrap());
+
+        let reader = io::BufReader::new("7,3,47\n55,2,20\n".as_bytes().chain(Unreadable));
+        assert!(!all_tickets_valid(&notes.rules, reader).unwrap());
+        let reader = io::BufReader::new("7,3,47\n".as_bytes().chain(Unreadable));
+        assert!(all_tickets_valid(&notes.rules, reader).is_err());
+        assert!(all_tickets_valid(&notes.rules, "7,,3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn it_sorts_rules_by_name_then_ranges() {
+        let mut rules = Notes::parse(TEST_INPUT).unwrap().rules;
+        rules.reverse();
+        rules.push(Rule::new("row".to_string(), 1..=2, 3..=4));
+        rules.sort();
+        let sorted: Vec<String> = rules.iter().map(|rule| rule.to_string()).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "class: 1-3 or 5-7",
+                "row: 1-2 or 3-4",
+                "row: 6-11 or 33-44",
+                "seat: 13-40 or 45-50",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_finds_the_gap_inside_a_rule() {
+        let rule = Rule::new("x".to_string(), 1..=3, 5..=7);
+        assert_eq!(rule.internal_gaps(), vec![4..=4]);
+        let rule = Rule::new("y".to_string(), 33..=44, 6..=11);
+        assert_eq!(rule.internal_gaps(), vec![12..=32]);
+        let rule = Rule::new("z".to_string(), 1..=4, 5..=7);
+        assert!(rule.internal_gaps().is_empty());
+    }
+
+    #[test]
+    fn it_finds_coverage_gaps() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(coverage_gaps(&notes.rules), vec![4..=4, 12..=12]);
+        assert_eq!(coverage_gaps(&notes.rules[2..]), vec![41..=44]);
+        assert!(coverage_gaps(&[]).is_empty());
+    }
+
+    #[test]
+    fn it_measures_rules_near_the_i64_limits() {
+        let wide = Rule::new("wide".to_string(), i64::MIN + 1..=-1, 1..=i64::MAX - 1);
+        let zero = Rule::new("zero".to_string(), 0..=0, 0..=0);
+        assert_eq!(wide.coverage(), (1 << 64) - 3);
+        assert!(!wide.overlaps(&zero));
+
+        let rules = vec![wide, zero];
+        assert_eq!(coverage_gaps(&rules[..1]), vec![0..=0]);
+        assert!(coverage_gaps(&rules).is_empty());
+        assert_eq!(
+            explain_rejection(&[i64::MAX, i64::MIN, 0], &rules),
+            vec![(0, i64::MAX), (1, i64::MIN)]
+        );
+        let (rule, range) = nearest_range(i64::MIN, &rules).unwrap();
+        assert_eq!((rule.name.as_str(), range), ("wide", &(i64::MIN + 1..=-1)));
+        assert_eq!(
+            find_all_valid_positions_cached(&rules, &[vec![i64::MAX - 1, 0]], rules.len()),
+            find_all_valid_positions(&rules, &[vec![i64::MAX - 1, 0]], rules.len())
+        );
+    }
+
+    #[test]
+    fn it_finds_equivalent_rules() {
+        let rules = vec![
+            Rule::new("class".to_string(), 1..=3, 5..=7),
+            Rule::new("row".to_string(), 6..=11, 33..=44),
+            Rule::new("seat".to_string(), 5..=7, 1..=3),
+            Rule::new("zone".to_string(), 1..=2, 3..=3),
+            Rule::new("wagon".to_string(), 1..=3, 1..=3),
+        ];
+        assert_eq!(
+            find_equivalent_rules(&rules),
+            vec![vec!["class", "seat"], vec!["zone", "wagon"]]
+        );
+        assert!(find_equivalent_rules(&rules[..2]).is_empty());
+    }
+
+    #[test]
+    fn it_validates_against_a_subset_of_rules() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let class = &notes.rules[0];
+        let row = &notes.rules[1];
+        let seat = &notes.rules[2];
+        assert!(is_valid_against(&[7, 3, 47], &[class, seat]));
+        assert!(!is_valid_against(&[7, 3, 47], &[class, row]));
+    }
+
+    #[test]
+    fn it_explains_a_rejected_ticket() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let rejection = explain_rejection(&[55, 2, 20], &notes.rules);
+        assert_eq!(rejection, vec![(0, 55)]);
+
+        let (rule, range) = nearest_range(55, &notes.rules).unwrap();
+        assert_eq!(rule.name, "seat");
+        assert_eq!(range, &(45..=50));
+    }
+
+    #[test]
+    fn it_collects_valid_positions() {
+        let mut lines = TEST_INPUT_TWO.lines().enumerate();
+        let rules = read_rules(&mut lines).unwrap();
+
+        read_passports(&mut lines).unwrap(); // read my passport
+        let near_passports = read_passports(&mut lines).unwrap();
+        let valid_passports = filter_invalid(near_passports, &rules);
+        let valid_positions = find_all_valid_positions(&rules, &valid_passports, rules.len());
+        assert_eq!(
+            valid_positions,
+            vec![
+                vec![(1, "class".to_string()), (2, "class".to_string())],
+                vec![
+                    (0, "row".to_string()),
+                    (1, "row".to_string()),
+                    (2, "row".to_string())
+                ],
+                vec![(2, "seat".to_string())]
+            ]
+        );
+    }
+
+    #[test]
+    fn it_determines_valid_positions() {
+        let mut lines = TEST_INPUT_TWO.lines().enumerate();
+        let rules = read_rules(&mut lines).unwrap();
+
+        read_passports(&mut lines).unwrap(); // read my passport
+        let near_passports = read_passports(&mut lines).unwrap();
+        let valid_passports = filter_invalid(near_passports, &rules);
+        let valid_positions = find_all_valid_positions(&rules, &valid_passports, rules.len());
+        let determined = determine_field_positions(valid_positions).unwrap();
+        assert_eq!(
+            determined,
+            vec![
+                (2, "seat".to_string()),
+                (1, "class".to_string()),
+                (0, "row".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn it_limits_the_number_of_candidates() {
+        let input = indoc! {"
+            a: 0-9 or 10-19
+            b: 0-9 or 10-19
+            c: 0-9 or 10-19
+
+            your ticket:
+            1,2,3
+
+            nearby tickets:
+            4,5,6
+        "};
+        let notes = Notes::parse(input).unwrap();
+        assert_eq!(
+            find_all_valid_positions_limited(
+                &notes.rules,
+                &notes.near_passports,
+                notes.num_columns(),
+                8
+            ),
+            Err(SolveError::TooManyCandidates { limit: 8 })
+        );
+        assert_eq!(
+            find_all_valid_positions_limited(
+                &notes.rules,
+                &notes.near_passports,
+                notes.num_columns(),
+                9
+            )
+            .unwrap(),
+            find_all_valid_positions(&notes.rules, &notes.near_passports, notes.num_columns())
+        );
+        assert_eq!(
+            solve_with_limit(&notes, 8),
+            Err(SolveError::TooManyCandidates { limit: 8 })
+        );
+        assert_eq!(solve_with_limit(&notes, 9), Err(SolveError::Ambiguous));
+
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(
+            solve_with_limit(&notes, 5),
+            Err(SolveError::TooManyCandidates { limit: 5 })
+        );
+        assert_eq!(solve_with_limit(&notes, 6), solve(&notes));
+    }
+
+    // The straightforward scan of every (rule, column) pair over every ticket.
+    fn valid_positions_by_rule(
+        rules: &[Rule],
+        passports: &[Vec<i64>],
+    ) -> Vec<Vec<(usize, String)>> {
+        rules
+            .iter()
+            .map(|rule| {
+                (0..rules.len())
+                    .filter(|position| is_valid_in_position(rule, *position, passports))
+                    .map(|position| (position, rule.name.clone()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skipping_ruled_out_pairs_keeps_the_positions() {
+        for input in &[TEST_INPUT_TWO, include_str!("../input.txt")] {
+            let notes = Notes::parse(input).unwrap();
+            let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+            assert_eq!(
+                find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns()),
+                valid_positions_by_rule(&notes.rules, &valid_passports)
+            );
+        }
+        let impossible = vec![vec![100, 100, 100], vec![1, 2, 3]];
+        let rules = Notes::parse(TEST_INPUT_TWO).unwrap().rules;
+        assert_eq!(
+            find_all_valid_positions(&rules, &impossible, rules.len()),
+            vec![vec![], vec![], vec![]]
+        );
+    }
+
+    #[test]
+    fn it_measures_candidate_density() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert!((candidate_density(&valid_positions) - 6.0 / 9.0).abs() < 1e-9);
+        assert_eq!(candidate_density(&[]), 0.0);
+    }
+
+    #[test]
+    fn it_finds_elimination_witnesses() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let witnesses = elimination_witnesses(&notes.rules, &valid_passports, notes.num_columns());
+        let expected: HashMap<(usize, usize), usize> = vec![((0, 0), 0), ((2, 0), 1), ((2, 1), 2)]
+            .into_iter()
+            .collect();
+        assert_eq!(witnesses, expected);
+    }
+
+    #[test]
+    fn it_finds_unconstrained_columns() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert_eq!(
+            unconstrained_columns(&notes.rules, &valid_passports, notes.num_columns()),
+            vec![2]
+        );
+        let mut free = valid_passports.clone();
+        free.push(vec![16, 0, 0]);
+        assert_eq!(
+            unconstrained_columns(&notes.rules, &free, notes.num_columns()),
+            vec![2]
+        );
+        free.push(vec![0, 4, 14]);
+        assert!(unconstrained_columns(&notes.rules, &free, notes.num_columns()).is_empty());
+    }
+
+    #[test]
+    fn it_lists_the_fully_ambiguous_rules() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert_eq!(
+            fully_ambiguous_rules(&valid_positions, 3),
+            vec!["row".to_string()]
+        );
+        assert!(fully_ambiguous_rules(&valid_positions[..1], 3).is_empty());
+    }
+
+    #[test]
+    fn it_sorts_the_candidate_set_sizes() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert_eq!(candidate_size_histogram(&valid_positions), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn it_estimates_the_search_space() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert_eq!(search_space_estimate(&valid_positions), 6);
+
+        let wide: Vec<Vec<(usize, String)>> = (0..40)
+            .map(|_| (0..40).map(|column| (column, "a".to_string())).collect())
+            .collect();
+        assert_eq!(search_space_estimate(&wide), u128::MAX);
+    }
+
+    #[test]
+    fn it_finds_non_constraining_tickets() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let mut valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert!(non_constraining_tickets(&notes.rules, &valid_passports).is_empty());
+
+        valid_passports.insert(1, vec![4, 8, 16]);
+        assert_eq!(
+            non_constraining_tickets(&notes.rules, &valid_passports),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn it_finds_redundant_tickets() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let mut valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert!(redundant_tickets(&notes.rules, &valid_passports, notes.num_columns()).is_empty());
+
+        valid_passports.push(vec![3, 9, 18]);
+        valid_passports.push(vec![1, 1, 1]);
+        assert_eq!(
+            redundant_tickets(&notes.rules, &valid_passports, notes.num_columns()),
+            vec![0, 3, 4]
+        );
+        let without: Vec<Vec<i64>> = valid_passports[1..].to_vec();
+        assert_eq!(
+            find_all_valid_positions(&notes.rules, &without, notes.num_columns()),
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns())
+        );
+    }
+
+    #[test]
+    fn it_builds_the_feasibility_grid() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert_eq!(
+            feasibility_grid(&notes.rules, &valid_passports, notes.num_columns()),
+            vec![
+                vec![false, true, true],
+                vec![true, true, true],
+                vec![false, false, true]
+            ]
+        );
+    }
+
+    #[test]
+    fn it_finds_the_tightest_column() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        assert_eq!(
+            tightest_column(&notes.rules, &valid_passports, notes.num_columns()),
+            Some((0, 1))
+        );
+        assert_eq!(tightest_column(&[], &valid_passports, 0), None);
+    }
+
+    #[test]
+    fn it_lists_feasible_columns() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert_eq!(feasible_columns(1, &valid_positions), vec![0, 1, 2]);
+        assert_eq!(feasible_columns(2, &valid_positions), vec![2]);
+    }
+
+    #[test]
+    fn it_detects_a_unique_solution() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert!(is_unique_solution(&valid_positions));
+
+        let symmetric = vec![
+            vec![(0, "class".to_string()), (1, "class".to_string())],
+            vec![(0, "row".to_string()), (1, "row".to_string())],
+        ];
+        assert!(!is_unique_solution(&symmetric));
+    }
+
+    #[test]
+    fn it_finds_unplaceable_rules() {
+        let input = indoc! {"
+            class: 0-1 or 4-19
+            row: 0-5 or 8-19
+            seat: 30-40 or 50-60
+
+            your ticket:
+            11,12,13
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+        "};
+        let notes = Notes::parse(input).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert_eq!(
+            unplaceable_rules(&notes.rules, &valid_positions),
+            vec!["seat".to_string()]
+        );
+    }
+
+    #[test]
+    fn it_solves_with_hints() {
+        let valid_positions = vec![
+            vec![(0, "class".to_string()), (1, "class".to_string())],
+            vec![(0, "row".to_string()), (1, "row".to_string())],
+            vec![(2, "seat".to_string())],
+        ];
+        assert_eq!(
+            determine_field_positions(valid_positions.clone()),
+            Err(SolveError::Ambiguous)
+        );
+
+        let mut solved = solve_with_hints(&valid_positions, &[("row".to_string(), 0)]).unwrap();
+        solved.sort_unstable();
+        assert_eq!(
+            solved,
+            vec![
+                (0, "row".to_string()),
+                (1, "class".to_string()),
+                (2, "seat".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_conflicting_hints() {
+        let valid_positions = vec![
+            vec![(0, "class".to_string()), (1, "class".to_string())],
+            vec![(0, "row".to_string()), (1, "row".to_string())],
+        ];
+        let hints = [("row".to_string(), 0), ("class".to_string(), 0)];
+        assert_eq!(
+            solve_with_hints(&valid_positions, &hints),
+            Err(SolveError::ConflictingHint("class".to_string()))
+        );
+        let hints = [("row".to_string(), 0), ("row".to_string(), 1)];
+        assert_eq!(
+            solve_with_hints(&valid_positions, &hints),
+            Err(SolveError::ConflictingHint("row".to_string()))
+        );
+        assert_eq!(
+            solve_with_hints(&valid_positions, &[("row".to_string(), 2)]),
+            Err(SolveError::ConflictingHint("row".to_string()))
+        );
+        assert_eq!(
+            solve_with_hints(&valid_positions, &[("seat".to_string(), 1)]),
+            Err(SolveError::UnknownField("seat".to_string()))
+        );
+    }
+
+    #[test]
+    fn it_makes_a_best_effort_at_an_ambiguous_solve() {
+        let valid_positions = vec![
+            vec![(2, "class".to_string()), (1, "class".to_string())],
+            vec![(2, "row".to_string()), (1, "row".to_string())],
+            vec![(0, "seat".to_string())],
+        ];
+        assert_eq!(
+            determine_field_positions(valid_positions.clone()),
+            Err(SolveError::Ambiguous)
+        );
+        let (assignment, unique) = solve_best_effort(&valid_positions);
+        assert!(!unique);
+        assert_eq!(
+            assignment,
+            vec![
+                (1, "class".to_string()),
+                (2, "row".to_string()),
+                (0, "seat".to_string())
+            ]
+        );
+
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let (mut assignment, unique) = solve_best_effort(&find_all_valid_positions(
+            &notes.rules,
+            &valid_passports,
+            notes.num_columns(),
+        ));
+        let mut solved = solve(&notes).unwrap();
+        assignment.sort_unstable();
+        solved.sort_unstable();
+        assert!(unique);
+        assert_eq!(assignment, solved);
+    }
+
+    #[test]
+    fn it_returns_a_partial_solution() {
+        // Seat is forced, which forces class, but row and zone could take
+        // either of the remaining columns.
+        let valid_positions = vec![
+            vec![(1, "class".to_string()), (2, "class".to_string())],
+            vec![(0, "row".to_string()), (3, "row".to_string())],
+            vec![(2, "seat".to_string())],
+            vec![
+                (0, "zone".to_string()),
+                (1, "zone".to_string()),
+                (3, "zone".to_string()),
+            ],
+        ];
+        assert_eq!(
+            solve_partial(&valid_positions),
+            PartialSolution {
+                determined: vec![(1, "class".to_string()), (2, "seat".to_string())],
+                ambiguous: vec![
+                    ("row".to_string(), vec![0, 3]),
+                    ("zone".to_string(), vec![0, 3]),
+                ],
+            }
+        );
+
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let partial = solve_partial(&find_all_valid_positions(
+            &notes.rules,
+            &valid_passports,
+            notes.num_columns(),
+        ));
+        assert!(partial.is_complete());
+        let mut solved = solve(&notes).unwrap();
+        solved.sort_unstable();
+        assert_eq!(partial.determined, solved);
+    }
+
+    #[test]
+    fn it_lists_the_remaining_columns_and_rules() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        assert_eq!(
+            remaining_state(&[(2, "seat".to_string())], &valid_positions),
+            (vec![0, 1], vec!["class".to_string(), "row".to_string()])
+        );
+        assert_eq!(remaining_state(&[], &valid_positions).0, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn it_multiplies_departures_for_each_personal_ticket() {
+        let input = indoc! {"
+            departure class: 0-1 or 4-19
+            row: 0-5 or 8-19
+            departure seat: 0-13 or 16-19
+
+            your ticket:
+            11,12,13
+            1,2,3
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+        "};
+        let notes = Notes::parse(input).unwrap();
+        assert_eq!(notes.my_passports, vec![vec![11, 12, 13], vec![1, 2, 3]]);
+        assert_eq!(departure_products(&notes), Ok(vec![12 * 13, 2 * 3]));
+        assert_eq!(departure_product(&notes), Ok(12 * 13));
+        assert_eq!(notes.to_input_string(), input);
+    }
+
+    #[test]
+    fn it_breaks_down_the_departure_product() {
+        let notes = Notes::parse(indoc! {"
+            departure seat: 0-13 or 16-19
+            row: 0-5 or 8-19
+            departure class: 0-1 or 4-19
+
+            your ticket:
+            11,12,13
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+        "})
+        .unwrap();
+        assert_eq!(
+            departure_breakdown(&notes),
+            Ok(vec![
+                ("departure class".to_string(), 12),
+                ("departure seat".to_string(), 13)
+            ])
+        );
+        assert_eq!(departure_product(&notes), Ok(12 * 13));
+    }
+
+    #[test]
+    fn it_aggregates_departure_fields() {
+        let notes = Notes::parse(indoc! {"
+            departure class: 0-1 or 4-19
+            departure row: 0-5 or 8-19
+            departure seat: 0-13 or 16-19
+
+            your ticket:
+            11,12,13
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+        "})
+        .unwrap();
+        let departure = FieldPrefix::default();
+        let aggregate = |op| aggregate_fields(&notes, &departure, op);
+        assert_eq!(aggregate(Aggregate::Sum), Ok(36));
+        assert_eq!(aggregate(Aggregate::Product), Ok(11 * 12 * 13));
+        assert_eq!(aggregate(Aggregate::Max), Ok(13));
+        assert_eq!(aggregate(Aggregate::Min), Ok(11));
+        assert_eq!(aggregate(Aggregate::Product), departure_product(&notes));
+
+        let arrival = FieldPrefix::new("arrival");
+        assert_eq!(aggregate_fields(&notes, &arrival, Aggregate::Sum), Ok(0));
+        assert_eq!(
+            aggregate_fields(&notes, &arrival, Aggregate::Max),
+            Err(SolveError::NoMatchingFields("arrival".to_string()))
+        );
+
+        let mut large = notes.clone();
+        large.my_passports = vec![vec![i64::MAX, 4_000_000_000, 3_000_000_000]];
+        let aggregate = |op| aggregate_fields(&large, &departure, op);
+        assert_eq!(aggregate(Aggregate::Sum), Err(SolveError::Overflow));
+        assert_eq!(aggregate(Aggregate::Product), Err(SolveError::Overflow));
+        assert_eq!(aggregate(Aggregate::Max), Ok(i64::MAX));
+    }
+
+    #[test]
+    fn it_matches_the_departure_prefix_ignoring_case() {
+        let notes = Notes::parse(indoc! {"
+            Departure class: 0-1 or 4-19
+            row: 0-5 or 8-19
+            departure seat: 0-13 or 16-19
+
+            your ticket:
+            11,12,13
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+        "})
+        .unwrap();
+        assert_eq!(departure_product(&notes), Ok(13));
+        assert_eq!(
+            prefix_product(&notes, &FieldPrefix::new("departure").ignore_case()),
+            Ok(12 * 13)
+        );
+        assert_eq!(
+            prefix_product(&notes, &FieldPrefix::new("DEPARTURE").ignore_case()),
+            Ok(12 * 13)
+        );
+    }
+
+    #[test]
+    fn it_reduces_a_large_product_modulo() {
+        let notes = Notes::parse(indoc! {"
+            departure a: 1000000000000-1999999999999
+            departure b: 2000000000000-2999999999999
+            departure c: 3000000000000-3999999999999
+
+            your ticket:
+            1000000000007,2000000000011,3000000000013
+
+            nearby tickets:
+            1000000000007,2000000000011,3000000000013
+        "})
+        .unwrap();
+        let departure = FieldPrefix::default();
+        assert_eq!(
+            departure_product_mod(&notes, &departure, 1_000_000_007),
+            Ok(945_123_379)
+        );
+        assert_eq!(departure_product_mod(&notes, &departure, 1), Ok(0));
+        assert_eq!(
+            departure_product_mod(&notes, &departure, 0),
+            Err(SolveError::InvalidModulus(0))
+        );
+        assert_eq!(
+            departure_product_mod(&notes, &departure, -7),
+            Err(SolveError::InvalidModulus(-7))
+        );
+    }
+
+    // A small xorshift generator, so that shuffles are repeatable per seed.
+    struct Shuffler(u64);
+
+    impl Shuffler {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn permutation(&mut self, len: usize) -> Vec<usize> {
+            let mut order: Vec<usize> = (0..len).collect();
+            for i in (1..len).rev() {
+                order.swap(i, (self.next() % (i as u64 + 1)) as usize);
+            }
+            order
+        }
+    }
+
+    #[test]
+    fn solving_does_not_depend_on_rule_or_column_order() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let mut expected = solve(&notes).unwrap();
+        expected.sort_unstable();
+
+        for seed in 1..=20 {
+            let mut shuffler = Shuffler(seed);
+            let rule_order = shuffler.permutation(notes.rules.len());
+            // Shuffled column `i` holds original column `columns[i]`.
+            let columns = shuffler.permutation(notes.rules.len());
+            let shuffle =
+                |passport: &Vec<i64>| columns.iter().map(|column| passport[*column]).collect();
+            let shuffled = Notes {
+                rules: rule_order
+                    .iter()
+                    .map(|rule| notes.rules[*rule].clone())
+                    .collect(),
+                my_passports: notes.my_passports.iter().map(shuffle).collect(),
+                near_passports: notes.near_passports.iter().map(shuffle).collect(),
+            };
+
+            let mut assignment: Vec<(usize, String)> = solve(&shuffled)
+                .unwrap()
+                .into_iter()
+                .map(|(column, name)| (columns[column], name))
+                .collect();
+            assignment.sort_unstable();
+            assert_eq!(assignment, expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn it_reduces_the_tickets_to_a_minimal_subset() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        notes.near_passports.insert(1, vec![3, 99, 18]);
+        notes.near_passports.push(vec![3, 9, 18]);
+        let subset = minimal_ticket_subset(&notes).unwrap();
+        assert_eq!(subset, vec![2, 3, 4]);
+
+        let expected = solve(&notes).unwrap();
+        notes.near_passports = subset
+            .iter()
+            .map(|index| notes.near_passports[*index].clone())
+            .collect();
+        assert_eq!(solve(&notes).unwrap(), expected);
+        for ticket in 0..notes.near_passports.len() {
+            let mut fewer = notes.near_passports.clone();
+            fewer.remove(ticket);
+            assert!(!is_unique_solution(&find_all_valid_positions(
+                &notes.rules,
+                &fewer,
+                notes.num_columns(),
+            )));
+        }
+    }
+
+    #[test]
+    fn it_solves_to_a_permutation_in_rule_order() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(solve_permutation(&notes), Ok(vec![1, 0, 2]));
+    }
+
+    #[test]
+    fn it_treats_poison_values_as_missing() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        notes.near_passports.push(vec![-1, 14, -1]);
+        let valid_passports =
+            filter_invalid_ignoring(notes.near_passports.clone(), &notes.rules, &[-1]);
+        assert_eq!(valid_passports.len(), 4);
+        assert_eq!(
+            filter_invalid(notes.near_passports.clone(), &notes.rules).len(),
+            3
+        );
+        assert_eq!(solve_ignoring(&notes, &[-1]), solve(&notes));
+    }
+
+    #[test]
+    fn it_masks_invalid_values_instead_of_dropping_tickets() {
+        let notes = Notes::parse(indoc! {"
+            a: 1-5
+            b: 1-10
+
+            your ticket:
+            1,2
+
+            nearby tickets:
+            3,3
+            7,99
+        "})
+        .unwrap();
+        assert_eq!(solve(&notes), Err(SolveError::Ambiguous));
+        assert_eq!(
+            solve_masked(&notes),
+            Ok(vec![(1, "a".to_string()), (0, "b".to_string())])
+        );
+
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(solve_masked(&notes), solve(&notes));
+    }
+
+    #[test]
+    fn it_asserts_a_solution() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(
+            assert_solution(&notes, &[("row", 0), ("class", 1), ("seat", 2)]),
+            Ok(())
+        );
+        assert_eq!(
+            assert_solution(&notes, &[("row", 1), ("class", 1), ("wagon", 2)]),
+            Err(vec![
+                "row: expected column 1, found 0".to_string(),
+                "wagon: expected column 2, not solved".to_string(),
+                "seat: found column 2, not expected".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_solves_to_names_in_column_order() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(
+            solve_to_columns(&notes),
+            Ok(vec![
+                "row".to_string(),
+                "class".to_string(),
+                "seat".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn it_iterates_named_fields_in_column_order() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        let ticket = Ticket::new(notes.my_passport().unwrap());
+        let named: Vec<(&str, i64)> = ticket.iter_named(&assignment).collect();
+        assert_eq!(named, vec![("row", 11), ("class", 12), ("seat", 13)]);
+
+        let short = Ticket::new(&[11, 12]);
+        let named: Vec<(&str, i64)> = short.iter_named(&assignment).collect();
+        assert_eq!(named, vec![("row", 11), ("class", 12)]);
+    }
+
+    #[test]
+    fn it_multiplies_named_fields() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        assert_eq!(product_of_fields(&notes, &["class", "row"]), Ok(12 * 11));
+        assert_eq!(
+            product_of_fields(&notes, &["class", "train"]),
+            Err(SolveError::UnknownField("train".to_string()))
+        );
+
+        let mut large = notes.clone();
+        large.my_passports = vec![vec![4_000_000_000, 3_000_000_000, 13]];
+        assert_eq!(
+            product_of_fields(&large, &["class", "row"]),
+            Err(SolveError::Overflow)
+        );
+    }
+
+    #[test]
+    fn it_parses_marker_delimited_sections() {
+        let notes = Notes::parse_with(MARKER_INPUT, &ParseOptions::with_marker("[")).unwrap();
+        assert_eq!(notes, Notes::parse(TEST_INPUT).unwrap());
+        assert_eq!(notes.my_passport(), Some(&[7, 1, 14][..]));
+        assert_eq!(notes.near_passports.len(), 4);
+    }
+
+    #[test]
+    fn it_validates_good_notes() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        assert_eq!(notes.validate(), Ok(()));
+    }
+
+    #[test]
+    fn it_reports_every_validation_error() {
+        let input = indoc! {"
+            class: 1-3 or 5-7
+            class: 6-11 or 33-44
+            seat: 40-13 or 45-50
+
+            your ticket:
+            7,1,14
+
+            nearby tickets:
+            7,3,47
+            40,4
+        "};
+        let notes = Notes::parse(input).unwrap();
+        assert_eq!(
+            notes.validate(),
+            Err(vec![
+                ValidationError::DuplicateName("class".to_string()),
+                ValidationError::InvalidRange {
+                    rule: "seat".to_string(),
+                    range: RangeInclusive::new(40, 13)
+                },
+                ValidationError::TicketLength {
+                    ticket: 1,
+                    expected: 3,
+                    found: 2
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_short_personal_ticket() {
+        let notes = Notes::parse(&TEST_INPUT.replace("7,1,14", "7,1")).unwrap();
+        let errors = notes.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![ValidationError::MyTicketLength {
+                ticket: 0,
+                expected: 3,
+                found: 2
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "your ticket 1 has 2 fields, expected 3"
+        );
+    }
+
+    #[test]
+    fn it_parses_ranges_with_dash_variants() {
+        let mut lines = "class: 1–3 or 5—7\nrow: −4-−2 or -1−6\ndepth: -10--5 or 0-0"
+            .lines()
+            .enumerate();
+        let rules = read_rules(&mut lines).unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                Rule::new("class".to_string(), 1..=3, 5..=7),
+                Rule::new("row".to_string(), -4..=-2, -1..=6),
+                Rule::new("depth".to_string(), -10..=-5, 0..=0),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_rust_style_ranges() {
+        let mut lines = "x: 1..=3 or 5..=7\ny: 1..3\nz: 0-4 or 6..9"
+            .lines()
+            .enumerate();
+        let rules = read_rules(&mut lines).unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                Rule::new("x".to_string(), 1..=3, 5..=7),
+                Rule::new("y".to_string(), 1..=2, 1..=2),
+                Rule::new("z".to_string(), 0..=4, 6..=8),
+            ]
+        );
+        assert!(rules[1].valid(&2));
+        assert!(!rules[1].valid(&3));
+    }
+
+    #[test]
+    fn it_handles_ranges_at_the_limits_of_i64() {
+        let mut lines = "big: -9223372036854775808--1 or 0-9223372036854775807"
+            .lines()
+            .enumerate();
+        let rule = read_rules(&mut lines).unwrap().remove(0);
+        assert_eq!(
+            rule,
+            Rule::new("big".to_string(), i64::MIN..=-1, 0..=i64::MAX)
+        );
+        assert!(rule.valid(&i64::MIN));
+        assert!(rule.valid(&i64::MAX));
+        assert_eq!(rule.coverage(), 1 << 64);
+        assert!(CachedRule::new(&rule).valid(&i64::MAX));
+
+        let top = Rule::new("top".to_string(), 0..=3, 5..=i64::MAX);
+        assert_eq!(top.coverage(), i64::MAX as u128);
+    }
+
+    #[test]
+    fn it_counts_overlapping_coverage_once() {
+        let rule = Rule::new("class".to_string(), 5..=10, 1..=7);
+        assert_eq!(rule.coverage(), 10);
+        assert_eq!(Rule::new("class".to_string(), 1..=3, 5..=7).coverage(), 6);
+    }
+
+    #[test]
+    fn it_intersects_overlapping_rules() {
+        let class = Rule::new("class".to_string(), 1..=3, 5..=7);
+        let row = Rule::new("row".to_string(), 2..=5, 7..=11);
+        assert!(class.overlaps(&row));
+        assert_eq!(class.intersection(&row), vec![2..=3, 5..=5, 7..=7]);
+    }
+
+    #[test]
+    fn it_finds_no_intersection_of_disjoint_rules() {
+        let class = Rule::new("class".to_string(), 1..=3, 5..=7);
+        let seat = Rule::new("seat".to_string(), 4..=4, 8..=10);
+        assert!(!class.overlaps(&seat));
+        assert!(class.intersection(&seat).is_empty());
+    }
+
+    #[test]
+    fn it_finds_the_symmetric_difference_of_rules() {
+        let low = Rule::new("low".to_string(), 1..=5, 1..=5);
+        let high = Rule::new("high".to_string(), 3..=7, 3..=7);
+        assert_eq!(low.symmetric_difference(&high), vec![1..=2, 6..=7]);
+        assert!(low.symmetric_difference(&low).is_empty());
+    }
+
+    #[test]
+    fn it_measures_the_similarity_of_rules() {
+        let low = Rule::new("low".to_string(), 1..=5, 1..=5);
+        let high = Rule::new("high".to_string(), 3..=7, 3..=7);
+        assert_eq!(low.jaccard(&high), 3.0 / 7.0);
+        assert_eq!(low.jaccard(&low), 1.0);
+        assert_eq!(
+            low.jaccard(&Rule::new("far".to_string(), 9..=9, 9..=9)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn it_reports_the_line_of_a_malformed_rule() {
+        let input = indoc! {"
+            class: 1-3 or 5-7
+            row: 6-11 or 33-44
+            seat: 13-40 or 45-99999999999999999999
+
+            your ticket:
+            7,1,14
+
+            nearby tickets:
+            7,3,47
+        "};
+        let err = Notes::parse(input).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::InvalidNumber {
+                value: "99999999999999999999".to_string(),
+                line: 3
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 3: invalid number 99999999999999999999"
+        );
+    }
+
+    #[test]
+    fn it_ignores_trailing_commas_in_tickets() {
+        let mut lines = "your ticket:\n7,1,14,\n7,1,14,,".lines().enumerate();
+        assert_eq!(
+            read_passports(&mut lines).unwrap(),
+            vec![vec![7, 1, 14], vec![7, 1, 14]]
+        );
+    }
+
+    #[test]
+    fn it_parses_a_block_of_rules() {
+        let rules = parse_rules(indoc! {"
+            class: 1-3 or 5-7
+            row: 6-11 or 33-44
+            seat: 13-40 or 45-50
+        "})
+        .unwrap();
+        assert_eq!(rules, Notes::parse(TEST_INPUT).unwrap().rules);
+        assert_eq!(parse_rules(""), Ok(vec![]));
+        assert_eq!(
+            parse_rules("class: 1-3 or 5-99999999999999999999"),
+            Err(ParseError::InvalidNumber {
+                value: "99999999999999999999".to_string(),
+                line: 1
+            })
+        );
+    }
+
+    #[test]
+    fn it_parses_the_parts_of_a_rule_line() {
+        let patterns = Patterns::default();
+        assert_eq!(
+            parse_rule_line("departure location: 1-3 or 5..8", 0, &patterns),
+            Ok(ParsedRuleLine {
+                name: "departure location".to_string(),
+                ranges: vec![(1, 3), (5, 7)],
+            })
+        );
+        assert_eq!(
+            parse_rule_line("seat: 13-40", 0, &patterns).unwrap().ranges,
+            vec![(13, 40)]
+        );
+        assert_eq!(
+            parse_rule_line("your ticket:", 4, &patterns),
+            Err(ParseError::UnexpectedLine {
+                text: "your ticket:".to_string(),
+                line: 5
+            })
+        );
+        assert_eq!(
+            parse_rule_line("x: 0x1-0x3 or 0x5-0x7", 0, &Patterns::hex())
+                .unwrap()
+                .ranges,
+            vec![(1, 3), (5, 7)]
+        );
+        assert!(parse_rule_line("x: 0x1-0x3", 0, &patterns).is_err());
+    }
+
+    #[test]
+    fn it_parses_hexadecimal_numbers() {
+        let input = indoc! {"
+            x: 0x1-0x3 or 0x5-0x7
+            y: 10-0x1f
+
+            your ticket:
+            0x2,0x1A
+
+            nearby tickets:
+            6,0x10
+        "};
+        let options = ParseOptions {
+            patterns: Patterns::hex(),
+            ..ParseOptions::default()
+        };
+        let notes = Notes::parse_with(input, &options).unwrap();
+        assert_eq!(
+            notes.rules,
+            vec![
+                Rule::new("x".to_string(), 1..=3, 5..=7),
+                Rule::new("y".to_string(), 10..=31, 10..=31),
+            ]
+        );
+        assert_eq!(notes.my_passport(), Some(&[2, 26][..]));
+        assert_eq!(notes.near_passports, vec![vec![6, 16]]);
+        assert!(Notes::parse(input).is_err());
+    }
+
+    #[test]
+    fn it_stitches_rules_and_tickets_together() {
+        let (rules, tickets) = TEST_INPUT.split_at(TEST_INPUT.find("your ticket").unwrap());
+        let input = stitch_input(rules.trim_end(), tickets);
+        assert_eq!(Notes::parse(&input), Notes::parse(TEST_INPUT));
+        assert_eq!(
+            stitch_input("class: 1-3 or 5-7\n\n\n", "\nyour ticket:\n1\n"),
+            "class: 1-3 or 5-7\n\nyour ticket:\n1\n"
+        );
+    }
+
+    #[test]
+    fn it_ignores_everything_after_an_end_line() {
+        let input = format!("{}END\n1,2,3\nthis is not a ticket\n", TEST_INPUT);
+        assert_eq!(Notes::parse(&input), Notes::parse(TEST_INPUT));
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(Notes::parse_with(&input, &strict), Notes::parse(TEST_INPUT));
+
+        let mut lines = "nearby tickets:\n1,2\nEND\n3,4\n".lines().enumerate();
+        assert_eq!(read_passports(&mut lines), Ok(vec![vec![1, 2]]));
+    }
+
+    #[test]
+    fn it_parses_rules_packed_onto_one_line() {
+        assert_eq!(
+            parse_rules("class: 1-3 or 5-7; row: 6-11 or 33-44"),
+            Ok(vec![
+                Rule::new("class".to_string(), 1..=3, 5..=7),
+                Rule::new("row".to_string(), 6..=11, 33..=44),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_parses_rule_weights() {
+        let rules = parse_rules(indoc! {"
+            departure location: 1-3 or 5-7 [w=5]
+            row: 6-11 or 33-44
+            seat: 13-40 [w=-2]
+        "})
+        .unwrap();
+        assert_eq!(
+            rules[0],
+            Rule::new("departure location".to_string(), 1..=3, 5..=7).with_weight(5)
+        );
+        assert_eq!(rules[1].weight, None);
+        assert_eq!(rules[2].weight, Some(-2));
+        assert_eq!(rules[0].to_string(), "departure location: 1-3 or 5-7 [w=5]");
+        assert_eq!(parse_rules(&rules[0].to_string()).unwrap()[0], rules[0]);
+    }
+
+    #[test]
+    fn it_parses_rules_with_a_custom_pattern() {
+        let input = TEST_INPUT_TWO
+            .replace("class:", "class =")
+            .replace("row:", "row =")
+            .replace("seat:", "seat =");
+        let rule = r"^(?P<name>[\w\s]+) = (?P<range_1_low>\d+)-(?P<range_1_high>\d+) or (?P<range_2_low>\d+)-(?P<range_2_high>\d+)$";
+        let patterns = Patterns {
+            rule: Regex::new(rule).unwrap(),
+            ..Patterns::default()
+        };
+
+        let mut lines = input.lines().enumerate();
+        let rules = read_rules_with(&mut lines, &patterns).unwrap();
+        assert_eq!(rules, Notes::parse(TEST_INPUT_TWO).unwrap().rules);
+
+        let options = ParseOptions {
+            patterns,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            Notes::parse_with(&input, &options),
+            Notes::parse(TEST_INPUT_TWO)
+        );
+        assert!(read_rules(&mut input.lines().enumerate())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn it_joins_tickets_wrapped_across_lines() {
+        let input = indoc! {"
+            [RULES]
+            class: 0-1 or 4-19
+            row: 0-5 or 8-19
+
+            seat: 0-13 or 16-19
+            [MY]
+            11,12,
+            13
+            [NEARBY]
+            3,9,
+            18
+
+            15,1,5
+
+            5,
+            14,
+            9
+        "};
+        let options = ParseOptions {
+            wrapped_tickets: true,
+            ..ParseOptions::with_marker("[")
+        };
+        let notes = Notes::parse_with(input, &options).unwrap();
+        assert_eq!(notes, Notes::parse(TEST_INPUT_TWO).unwrap());
+        assert_eq!(notes.my_passport(), Some(&[11, 12, 13][..]));
+    }
+
+    #[test]
+    fn it_rejects_wrapped_tickets_without_a_marker() {
+        let options = ParseOptions {
+            wrapped_tickets: true,
+            ..ParseOptions::default()
+        };
+        let err = Notes::parse_with(TEST_INPUT_TWO, &options).unwrap_err();
+        assert_eq!(err, ParseError::WrappedWithoutMarker { line: 1 });
+        assert_eq!(
+            err.to_string(),
+            "line 1: wrapped tickets need a section marker"
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_lines_lenient_mode_stops_at() {
+        let garbled = TEST_INPUT_TWO.replace("row:", "row");
+        let lenient = Notes::parse(&garbled).unwrap();
+        assert_eq!(lenient.rules.len(), 1);
+
+        let strict = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            Notes::parse_with(&garbled, &strict),
+            Err(ParseError::UnexpectedLine {
+                text: "row 0-5 or 8-19".to_string(),
+                line: 2
+            })
+        );
+        assert_eq!(
+            Notes::parse_with(TEST_INPUT_TWO, &strict),
+            Notes::parse(TEST_INPUT_TWO)
+        );
+
+        let ticket = TEST_INPUT_TWO.replace("15,1,5", "fifteen");
+        let err = Notes::parse_with(&ticket, &strict).unwrap_err();
+        assert_eq!(err.to_string(), r#"line 10: unexpected line "fifteen""#);
+    }
+
+    #[test]
+    fn it_checks_a_declared_field_count() {
+        let declared = format!("fields: 3\n{}", TEST_INPUT_TWO);
+        assert_eq!(Notes::parse(&declared), Notes::parse(TEST_INPUT_TWO));
+        let paragraph = format!("fields: 3\n\n{}", TEST_INPUT_TWO);
+        assert_eq!(Notes::parse(&paragraph), Notes::parse(TEST_INPUT_TWO));
+
+        let too_many = format!("fields: 4\n{}", TEST_INPUT_TWO);
+        assert_eq!(
+            Notes::parse(&too_many),
+            Err(ParseError::FieldCount {
+                expected: 4,
+                found: 3,
+                line: 5
+            })
+        );
+
+        let truncated = format!("fields: 3\n{}", TEST_INPUT_TWO.replace("15,1,5", "15,1"));
+        let err = Notes::parse(&truncated).unwrap_err();
+        assert_eq!(err.to_string(), "line 11: expected 3 fields, found 2");
+    }
+
+    #[test]
+    fn it_rejects_interior_empty_fields_in_tickets() {
+        let mut lines = "your ticket:\n7,1,14\n7,,14".lines().enumerate();
+        assert_eq!(
+            read_passports(&mut lines),
+            Err(ParseError::EmptyField { line: 3 })
+        );
+    }
+
+    #[test]
+    fn it_reports_the_line_of_a_missing_section() {
+        let err = Notes::parse("class: 1-3 or 5-7\n\nyour ticket:\n").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::MissingSection {
+                section: "your ticket",
+                line: 4
+            }
+        );
+    }
+
+    #[test]
+    fn it_parses_ticket_sections_in_either_order() {
+        let input = indoc! {"
+            class: 0-1 or 4-19
+            row: 0-5 or 8-19
+            seat: 0-13 or 16-19
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+
+            your ticket:
+            11,12,13
+        "};
+        let notes = Notes::parse(input).unwrap();
+        assert_eq!(notes, Notes::parse(TEST_INPUT_TWO).unwrap());
+
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        let determined = determine_field_positions(valid_positions).unwrap();
+        assert_eq!(
+            determined,
+            vec![
+                (2, "seat".to_string()),
+                (1, "class".to_string()),
+                (0, "row".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_lines_from_a_slice() {
+        let lines: Vec<&str> = TEST_INPUT.lines().collect();
+        assert_eq!(
+            Notes::from_lines(&lines).unwrap(),
+            Notes::parse(TEST_INPUT).unwrap()
+        );
+
+        let owned: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        assert_eq!(
+            Notes::from_lines(&owned).unwrap(),
+            Notes::parse(TEST_INPUT).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_separates_io_errors_from_parse_errors() {
+        let missing = std::env::temp_dir().join("adv2020_16_missing_input.txt");
+        match Notes::read_from(missing.to_str().unwrap()) {
+            Err(Error::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::NotFound),
+            other => panic!("expected an I/O error, got {:?}", other),
+        }
+
+        let broken = std::env::temp_dir().join("adv2020_16_broken_input.txt");
+        fs::write(&broken, "class: 1-3 or 5-7\n").unwrap();
+        let result = Notes::read_from(broken.to_str().unwrap());
+        fs::remove_file(&broken).unwrap();
+        match result {
+            Err(Error::Parse(err)) => assert_eq!(
+                err,
+                ParseError::MissingSection {
+                    section: "your ticket",
+                    line: 2
+                }
+            ),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_describes_http_errors() {
+        let err = Error::Http("connection refused".to_string());
+        assert_eq!(err.to_string(), "could not fetch input: connection refused");
+        assert!(error::Error::source(&err).is_none());
+    }
+
+    #[test]
+    fn it_skips_a_byte_order_mark() {
+        let input = format!("\u{feff}{}", TEST_INPUT);
+        assert_eq!(
+            Notes::parse(&input).unwrap(),
+            Notes::parse(TEST_INPUT).unwrap()
+        );
+
+        let path = std::env::temp_dir().join("adv2020_16_bom_input.txt");
+        fs::write(&path, &input).unwrap();
+        let notes = Notes::read_from(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(notes.unwrap(), Notes::parse(TEST_INPUT).unwrap());
+    }
+
+    #[test]
+    fn it_round_trips_notes_through_text() {
+        let notes = Notes::parse(TEST_INPUT).unwrap();
+        let text = notes.to_input_string();
+        assert_eq!(text, TEST_INPUT);
+        assert_eq!(Notes::parse(&text).unwrap(), notes);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn it_round_trips_notes_through_bincode() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let bytes = notes.to_bincode().unwrap();
+        let cached = Notes::from_bincode(&bytes).unwrap();
+        assert_eq!(cached, notes);
+        assert_eq!(solve(&cached), solve(&notes));
+    }
+
+    #[test]
+    fn it_parses_form_feed_delimited_sections() {
+        let input = MARKER_INPUT.replace("[RULES]\n", "").replace('[', "\x0c[");
+        let notes = Notes::parse_with(&input, &ParseOptions::with_marker("\x0c")).unwrap();
+        assert_eq!(notes, Notes::parse(TEST_INPUT).unwrap());
+    }
+}
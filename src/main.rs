@@ -1,158 +1,303 @@
-use std::collections::HashSet;
-use std::ops::RangeInclusive;
+use std::env;
+use std::process;
+use std::time::Instant;
 
-use lazy_static::lazy_static;
-use regex::{Captures, Regex};
+use adv2020_16::{
+    assignment_breakdown, filter_invalid, find_all_invalid_fields, format_passport,
+    format_solution, format_solution_csv, format_solution_json, read_input, solve, solve_timed,
+    stitch_input, Error, FieldPrefix, FormatOptions, Notes, SolveError, SolveTimings,
+};
 
-fn main() {
-    let mut lines = include_str!("../input.txt").lines();
-    let rules = read_rules(&mut lines);
-
-    let my_passport = read_passports(&mut lines).first().unwrap().clone();
-    let near_passports = read_passports(&mut lines);
-    let invalid_fields = find_all_invalid_fields(&near_passports, &rules);
-    println!(
-        "answer 1 is {}",
-        invalid_fields.iter().map(|field| **field).sum::<i64>()
-    );
-
-    let valid_passports = filter_invalid(near_passports, &rules);
-    let valid_positions = find_all_valid_positions(&rules, &valid_passports);
-    let determined_positions = determine_field_positions(valid_positions);
-    let part_2: i64 = determined_positions
-        .iter()
-        .filter(|(_, name)| name.starts_with("departure"))
-        .map(|(field, _)| my_passport[*field])
-        .product();
-
-    println!("part 2: {}", part_2);
+const USAGE: &str = "usage: adv2020_16 [--check] [--part 1|2|both] \
+                     [--table | --json | --csv | --emit-valid] \
+                     [--one-based] [--prefix name] [--ignore-case] [--breakdown] \
+                     [--limit N] [--timing] [--quiet] [--my-ticket a,b,c] \
+                     [input | - | url | --rules path --tickets path]";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Part {
+    One,
+    Two,
+    #[default]
+    Both,
 }
 
-#[derive(Debug)]
-struct Rule {
-    name: String,
-    range1: RangeInclusive<i64>,
-    range2: RangeInclusive<i64>,
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum Output {
+    #[default]
+    Answers,
+    Table,
+    Json,
+    Csv,
+    Valid,
 }
 
-impl Rule {
-    fn new(name: String, range1: RangeInclusive<i64>, range2: RangeInclusive<i64>) -> Rule {
-        Rule {
-            name,
-            range1,
-            range2,
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    check_only: bool,
+    part: Part,
+    output: Output,
+    format: FormatOptions,
+    prefix: FieldPrefix,
+    breakdown: bool,
+    /// Prints each answer as a bare number on its own line.
+    quiet: bool,
+    /// Only the first this many nearby tickets are used. With fewer tickets
+    /// there is less to rule columns out, so a unique assignment may become
+    /// ambiguous.
+    limit: Option<usize>,
+    /// Replaces my ticket from the input, keeping its rules and nearby
+    /// tickets.
+    my_ticket: Option<Vec<i64>>,
+    /// Reports how long parsing and each phase of the solve took, after the
+    /// answers. Phases which did not run, such as the solve for part one on
+    /// its own, show as zero.
+    timing: bool,
+    path: Option<String>,
+    /// Separate files holding the rules and the ticket sections, read in place
+    /// of `path`.
+    rules_path: Option<String>,
+    tickets_path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => options.check_only = true,
+            "--table" => options.output = Output::Table,
+            "--json" => options.output = Output::Json,
+            "--csv" => options.output = Output::Csv,
+            "--emit-valid" => options.output = Output::Valid,
+            "--one-based" => options.format.one_based = true,
+            "--ignore-case" => options.prefix.ignore_case = true,
+            "--breakdown" => options.breakdown = true,
+            "--timing" => options.timing = true,
+            "--quiet" => options.quiet = true,
+            "--limit" => {
+                options.limit = match args.next() {
+                    Some(limit) => Some(
+                        limit
+                            .parse()
+                            .map_err(|_| format!("invalid limit {}", limit))?,
+                    ),
+                    None => return Err("--limit needs a value".to_string()),
+                }
+            }
+            "--my-ticket" => {
+                options.my_ticket = match args.next() {
+                    Some(ticket) => Some(
+                        ticket
+                            .split(',')
+                            .map(|field| field.trim().parse())
+                            .collect::<Result<_, _>>()
+                            .map_err(|_| format!("invalid ticket {}", ticket))?,
+                    ),
+                    None => return Err("--my-ticket needs a value".to_string()),
+                }
+            }
+            "--rules" => match args.next() {
+                Some(path) => options.rules_path = Some(path),
+                None => return Err("--rules needs a value".to_string()),
+            },
+            "--tickets" => match args.next() {
+                Some(path) => options.tickets_path = Some(path),
+                None => return Err("--tickets needs a value".to_string()),
+            },
+            "--prefix" => match args.next() {
+                Some(prefix) => options.prefix.prefix = prefix,
+                None => return Err("--prefix needs a value".to_string()),
+            },
+            "--part" => {
+                options.part = match args.next().as_deref() {
+                    Some("1") => Part::One,
+                    Some("2") => Part::Two,
+                    Some("both") => Part::Both,
+                    Some(other) => return Err(format!("unknown part {}", other)),
+                    None => return Err("--part needs a value".to_string()),
+                }
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {}", flag)),
+            path if options.path.is_none() => options.path = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument {}", extra)),
         }
     }
-
-    fn valid(&self, field: &i64) -> bool {
-        self.range1.contains(field) || self.range2.contains(field)
+    match (&options.rules_path, &options.tickets_path) {
+        (Some(_), None) => return Err("--rules needs --tickets".to_string()),
+        (None, Some(_)) => return Err("--tickets needs --rules".to_string()),
+        (Some(_), Some(_)) if options.path.is_some() => {
+            return Err("--rules and --tickets replace the input".to_string())
+        }
+        _ => {}
     }
+    Ok(options)
 }
 
-lazy_static! {
-    static ref RULE_REGEX: Regex = Regex::new(r"(?P<name>[\w\s]+): (?P<range_1_low>\d+)-(?P<range_1_high>\d+) or (?P<range_2_low>\d+)-(?P<range_2_high>\d+)$").unwrap();
-    static ref FIELD_REGEX: Regex = Regex::new(r"(?P<value>\d+),?").unwrap();
+fn read_or_exit(path: &str) -> String {
+    read_input(path).unwrap_or_else(|err| {
+        eprintln!("could not read {}: {}", path, err);
+        process::exit(1);
+    })
 }
 
-fn read_rules<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Vec<Rule> {
-    let mut rules: Vec<Rule> = Vec::new();
-    for line in lines {
-        if let Some(rule_caps) = RULE_REGEX.captures(line) {
-            let range_1_low = rule_caps["range_1_low"].parse::<i64>().unwrap();
-            let range_1_high = rule_caps["range_1_high"].parse::<i64>().unwrap();
-            let range_2_low: i64 = rule_caps["range_2_low"].parse::<i64>().unwrap();
-            let range_2_high: i64 = rule_caps["range_2_high"].parse::<i64>().unwrap();
-            rules.push(Rule::new(
-                rule_caps["name"].to_string(),
-                range_1_low..=range_1_high,
-                range_2_low..=range_2_high,
-            ))
-        } else {
-            break;
+fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+    let input = match &options.path {
+        #[cfg(feature = "http")]
+        Some(url) if adv2020_16::is_url(url) => adv2020_16::fetch(url).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        }),
+        Some(path) => read_or_exit(path),
+        None => match (&options.rules_path, &options.tickets_path) {
+            (Some(rules), Some(tickets)) => {
+                stitch_input(&read_or_exit(rules), &read_or_exit(tickets))
+            }
+            _ => include_str!("../input.txt").to_string(),
+        },
+    };
+
+    if options.check_only {
+        let (code, report) = check(&input);
+        print!("{}", report);
+        process::exit(code);
+    }
+
+    match run(&input, &options) {
+        Ok(output) => print!("{}", output),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
         }
     }
-    rules
 }
 
-fn read_passports<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Vec<Vec<i64>> {
-    let mut passports = Vec::new();
-    lines.next();
-    for line in lines {
-        let caps: Vec<Captures> = FIELD_REGEX.captures_iter(line).collect();
-        if !caps.is_empty() {
-            let mut passport = Vec::new();
-            for field in caps.into_iter() {
-                passport.push(field["value"].parse::<i64>().unwrap());
-            }
-            passports.push(passport)
-        } else {
-            break;
+// Solves the requested parts, returning the output rather than printing it.
+fn run(input: &str, options: &Options) -> Result<String, Error> {
+    let start = Instant::now();
+    let mut notes = Notes::parse(input)?;
+    let parse = start.elapsed();
+    if let Some(limit) = options.limit {
+        notes.near_passports.truncate(limit);
+    }
+    if let Some(ticket) = &options.my_ticket {
+        if ticket.len() != notes.num_rules() {
+            return Err(Error::Solve(SolveError::MyTicketLength {
+                ticket: 0,
+                expected: notes.num_rules(),
+                found: ticket.len(),
+            }));
         }
+        notes.my_passports = vec![ticket.clone()];
     }
-    passports
-}
+    let mut output = String::new();
 
-fn find_invalid_fields<'a>(passport: &'a [i64], rules: &[Rule]) -> Vec<&'a i64> {
-    let fields = passport
-        .iter()
-        .filter(|field| rules.iter().all(|rule| !rule.valid(*field)))
-        .collect();
-    fields
-}
+    if options.output == Output::Valid {
+        for passport in filter_invalid(notes.near_passports, &notes.rules) {
+            output.push_str(&format_passport(&passport));
+            output.push('\n');
+        }
+        return Ok(output);
+    }
 
-fn find_all_invalid_fields<'a>(passports: &'a [Vec<i64>], rules: &[Rule]) -> Vec<&'a i64> {
-    passports
-        .iter()
-        .map(|passport| find_invalid_fields(&passport, rules))
-        .filter(|invalid_fields| !invalid_fields.is_empty())
-        .flatten()
-        .collect()
-}
+    // Everything but part one on its own needs the assignment, which is solved
+    // once and timed when asked.
+    let mut timings = SolveTimings {
+        parse,
+        ..SolveTimings::default()
+    };
+    let assignment = if options.output == Output::Answers && options.part == Part::One {
+        Vec::new()
+    } else if options.timing {
+        let (assignment, solve_timings) = solve_timed(&notes)?;
+        timings = SolveTimings {
+            parse,
+            ..solve_timings
+        };
+        assignment
+    } else {
+        solve(&notes)?
+    };
 
-fn filter_invalid(passports: Vec<Vec<i64>>, rules: &[Rule]) -> Vec<Vec<i64>> {
-    passports
-        .into_iter()
-        .filter(|passport| find_invalid_fields(passport, rules).is_empty())
-        .collect()
-}
+    match options.output {
+        Output::Table => output.push_str(&format_solution(&notes, &assignment, &options.format)?),
+        Output::Csv => output.push_str(&format_solution_csv(&notes, &assignment, &options.format)?),
+        Output::Json => {
+            output.push_str(&format_solution_json(&notes, &assignment, &options.format)?);
+            output.push('\n');
+        }
+        _ => answer(&notes, &assignment, options, &mut output)?,
+    }
 
-fn is_valid_in_position(rule: &Rule, position: usize, passports: &[Vec<i64>]) -> bool {
-    passports
-        .iter()
-        .all(|passport| rule.valid(&passport[position]))
+    if options.timing {
+        output.push_str(&timings.to_string());
+    }
+    Ok(output)
 }
 
-fn find_all_valid_positions(rules: &[Rule], passports: &[Vec<i64>]) -> Vec<Vec<(usize, String)>> {
-    let mut positions = Vec::new();
-    for rule in rules {
-        let mut rule_positions = Vec::new();
-        for position in 0..rules.len() {
-            if is_valid_in_position(rule, position, passports) {
-                rule_positions.push((position, rule.name.clone()));
-            }
+// Appends the requested answers, using `assignment` for part two.
+fn answer(
+    notes: &Notes,
+    assignment: &[(usize, String)],
+    options: &Options,
+    output: &mut String,
+) -> Result<(), SolveError> {
+    if options.part != Part::Two {
+        let invalid_fields = find_all_invalid_fields(&notes.near_passports, &notes.rules);
+        // Summed as i128 so that a few invalid values near i64::MAX cannot
+        // overflow.
+        let part_1: i128 = invalid_fields.iter().map(|field| **field as i128).sum();
+        if options.quiet {
+            output.push_str(&format!("{}\n", part_1));
+        } else {
+            output.push_str(&format!("answer 1 is {}\n", part_1));
         }
-        positions.push(rule_positions);
     }
-    positions
-}
 
-fn determine_field_positions(mut all_positions: Vec<Vec<(usize, String)>>) -> Vec<(usize, String)> {
-    let mut determined_positions = Vec::new();
-    let mut taken = HashSet::new();
-    all_positions.sort_unstable_by(|a, b| a.len().cmp(&b.len()));
-    for (i, positions) in all_positions.into_iter().enumerate() {
-        if positions.len() != i + 1 {
-            panic!("too big!");
-        }
-        for (position, name) in positions {
-            if !taken.contains(&position) {
-                determined_positions.push((position, name));
-                taken.insert(position);
-                break;
+    if options.part != Part::One {
+        let breakdown = assignment_breakdown(notes, assignment, &options.prefix)?;
+        if options.breakdown && !options.quiet {
+            for (name, value) in &breakdown {
+                output.push_str(&format!("{}: {}\n", name, value));
             }
         }
+        let part_2: i64 = breakdown.iter().map(|(_, value)| value).product();
+        if options.quiet {
+            output.push_str(&format!("{}\n", part_2));
+        } else {
+            output.push_str(&format!("part 2: {}\n", part_2));
+        }
+    }
+    Ok(())
+}
+
+// Parses and validates the input without solving it, returning the exit code
+// and a report of any problems found.
+fn check(input: &str) -> (i32, String) {
+    let notes = match Notes::parse(input) {
+        Ok(notes) => notes,
+        Err(err) => return (1, format!("parse error: {}\n", err)),
+    };
+    match notes.validate() {
+        Ok(()) => (
+            0,
+            format!(
+                "ok: {} rules, {} nearby tickets\n",
+                notes.rules.len(),
+                notes.near_passports.len()
+            ),
+        ),
+        Err(errors) => (
+            1,
+            errors
+                .iter()
+                .map(|err| format!("invalid: {}\n", err))
+                .collect(),
+        ),
     }
-    determined_positions
 }
 
 #[cfg(test)]
@@ -165,10 +310,10 @@ mod tests {
         class: 1-3 or 5-7
         row: 6-11 or 33-44
         seat: 13-40 or 45-50
-        
+
         your ticket:
         7,1,14
-        
+
         nearby tickets:
         7,3,47
         40,4,50
@@ -191,56 +336,333 @@ mod tests {
     "};
 
     #[test]
-    fn it_collects_correct_invalid_fields() {
-        let mut lines = TEST_INPUT.lines();
-        let rules = read_rules(&mut lines);
+    fn it_prints_the_answers_for_test_input() {
+        assert_eq!(
+            run(TEST_INPUT, &Options::default()).unwrap(),
+            "answer 1 is 71\npart 2: 1\n"
+        );
+    }
 
-        read_passports(&mut lines); // read my passport
-        let near_passports = read_passports(&mut lines);
-        let invalid_fields = find_all_invalid_fields(&near_passports, &rules);
-        assert_eq!(invalid_fields, vec![&4, &55, &12]);
+    #[test]
+    fn it_prints_the_answers_for_test_input_two() {
+        assert_eq!(
+            run(TEST_INPUT_TWO, &Options::default()).unwrap(),
+            "answer 1 is 0\npart 2: 1\n"
+        );
+    }
+
+    fn options(args: &[&str]) -> Options {
+        parse_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn it_parses_part_selection() {
+        assert_eq!(options(&[]).part, Part::Both);
+        assert_eq!(options(&["--part", "1"]).part, Part::One);
+        assert_eq!(options(&["--part", "2", "input.txt"]).part, Part::Two);
+        assert_eq!(options(&["--part", "both"]).part, Part::Both);
+        assert!(parse_args(["--part", "3"].iter().map(|arg| arg.to_string())).is_err());
+    }
+
+    #[test]
+    fn it_prints_only_part_one() {
+        assert_eq!(
+            run(TEST_INPUT, &options(&["--part", "1"])).unwrap(),
+            "answer 1 is 71\n"
+        );
     }
 
     #[test]
-    fn it_collects_valid_positions() {
-        let mut lines = TEST_INPUT_TWO.lines();
-        let rules = read_rules(&mut lines);
+    fn it_prints_only_part_two() {
+        assert_eq!(
+            run(TEST_INPUT, &options(&["--part", "2"])).unwrap(),
+            "part 2: 1\n"
+        );
+    }
 
-        read_passports(&mut lines); // read my passport
-        let near_passports = read_passports(&mut lines);
-        let valid_passports = filter_invalid(near_passports, &rules);
-        let valid_positions = find_all_valid_positions(&rules, &valid_passports);
+    #[test]
+    fn it_prints_both_parts() {
         assert_eq!(
-            valid_positions,
-            vec![
-                vec![(1, "class".to_string()), (2, "class".to_string())],
-                vec![
-                    (0, "row".to_string()),
-                    (1, "row".to_string()),
-                    (2, "row".to_string())
-                ],
-                vec![(2, "seat".to_string())]
-            ]
+            run(TEST_INPUT, &options(&["--part", "both"])).unwrap(),
+            "answer 1 is 71\npart 2: 1\n"
         );
     }
 
     #[test]
-    fn it_determines_valid_positions() {
-        let mut lines = TEST_INPUT_TWO.lines();
-        let rules = read_rules(&mut lines);
+    fn it_filters_by_a_case_insensitive_prefix() {
+        let input = TEST_INPUT_TWO.replace("class:", "Departure class:");
+        let args = ["--part", "2", "--prefix", "departure"];
+        assert_eq!(run(&input, &options(&args)).unwrap(), "part 2: 1\n");
 
-        read_passports(&mut lines); // read my passport
-        let near_passports = read_passports(&mut lines);
-        let valid_passports = filter_invalid(near_passports, &rules);
-        let valid_positions = find_all_valid_positions(&rules, &valid_passports);
-        let determined = determine_field_positions(valid_positions);
+        let args = ["--part", "2", "--prefix", "departure", "--ignore-case"];
+        assert_eq!(run(&input, &options(&args)).unwrap(), "part 2: 12\n");
+        assert!(parse_args(["--prefix"].iter().map(|arg| arg.to_string())).is_err());
+    }
+
+    #[test]
+    fn it_prints_the_departure_breakdown() {
+        let input = TEST_INPUT_TWO
+            .replace("class:", "departure class:")
+            .replace("seat:", "departure seat:");
         assert_eq!(
-            determined,
-            vec![
-                (2, "seat".to_string()),
-                (1, "class".to_string()),
-                (0, "row".to_string())
-            ]
+            run(&input, &options(&["--part", "2", "--breakdown"])).unwrap(),
+            indoc! {"
+                departure class: 12
+                departure seat: 13
+                part 2: 156
+            "}
         );
     }
+
+    #[test]
+    fn it_limits_the_nearby_tickets() {
+        assert_eq!(options(&["--limit", "2"]).limit, Some(2));
+        assert!(parse_args(["--limit", "two"].iter().map(|arg| arg.to_string())).is_err());
+        assert_eq!(
+            run(TEST_INPUT, &options(&["--part", "1", "--limit", "2"])).unwrap(),
+            "answer 1 is 4\n"
+        );
+        assert_eq!(
+            run(TEST_INPUT, &options(&["--part", "1", "--limit", "0"])).unwrap(),
+            "answer 1 is 0\n"
+        );
+    }
+
+    #[test]
+    fn it_reports_timings_after_the_answers() {
+        let output = run(TEST_INPUT_TWO, &options(&["--part", "2", "--timing"])).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "part 2: 1");
+        for (line, phase) in
+            lines[1..]
+                .iter()
+                .zip(&["parse", "filter", "candidates", "assign", "total"])
+        {
+            assert!(line.starts_with(&format!("{}: ", phase)));
+        }
+        assert_eq!(lines.len(), 6);
+    }
+
+    #[test]
+    fn it_times_the_solve_behind_the_answers() {
+        let output = run(AMBIGUOUS_INPUT, &options(&["--part", "1", "--timing"])).unwrap();
+        assert_eq!(output.lines().next(), Some("answer 1 is 0"));
+        assert!(output.contains("\nassign: 0ns\n"));
+        match run(TEST_INPUT_TWO, &options(&["--timing", "--limit", "1"])) {
+            Err(Error::Solve(SolveError::Ambiguous)) => {}
+            other => panic!("expected a solve error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_prints_bare_answers_when_quiet() {
+        assert_eq!(run(TEST_INPUT, &options(&["--quiet"])).unwrap(), "71\n1\n");
+        let input = TEST_INPUT_TWO.replace("class:", "departure class:");
+        assert_eq!(
+            run(&input, &options(&["--quiet", "--breakdown"])).unwrap(),
+            "0\n12\n"
+        );
+    }
+
+    #[test]
+    fn it_reads_rules_and_tickets_from_separate_files() {
+        let parsed = options(&["--rules", "rules.txt", "--tickets", "tickets.txt"]);
+        assert_eq!(parsed.rules_path.as_deref(), Some("rules.txt"));
+        assert_eq!(parsed.tickets_path.as_deref(), Some("tickets.txt"));
+        assert_eq!(parsed.path, None);
+        for args in [
+            &["--rules", "rules.txt"][..],
+            &["--tickets", "tickets.txt"],
+            &[
+                "--rules",
+                "rules.txt",
+                "--tickets",
+                "tickets.txt",
+                "input.txt",
+            ],
+        ] {
+            assert!(parse_args(args.iter().map(|arg| arg.to_string())).is_err());
+        }
+    }
+
+    #[test]
+    fn it_overrides_my_ticket() {
+        assert_eq!(
+            options(&["--my-ticket", "7,1,14"]).my_ticket,
+            Some(vec![7, 1, 14])
+        );
+        assert!(parse_args(["--my-ticket", "7,x"].iter().map(|arg| arg.to_string())).is_err());
+
+        let input = TEST_INPUT_TWO.replace("class:", "departure class:");
+        assert_eq!(
+            run(&input, &options(&["--part", "2"])).unwrap(),
+            "part 2: 12\n"
+        );
+        assert_eq!(
+            run(&input, &options(&["--part", "2", "--my-ticket", "1,2,3"])).unwrap(),
+            "part 2: 2\n"
+        );
+        for (ticket, found) in &[("1,2,3,4", 4), ("1,2", 2)] {
+            for part in &["1", "2"] {
+                match run(&input, &options(&["--part", part, "--my-ticket", ticket])) {
+                    Err(Error::Solve(SolveError::MyTicketLength {
+                        ticket: 0,
+                        expected: 3,
+                        found: actual,
+                    })) if actual == *found => {}
+                    other => panic!("expected a ticket length error, got {:?}", other),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_sums_invalid_values_near_the_i64_limit() {
+        let input = indoc! {"
+            class: 1-3 or 5-7
+
+            your ticket:
+            1
+
+            nearby tickets:
+            9223372036854775807
+            9223372036854775807
+        "};
+        assert_eq!(
+            run(input, &options(&["--part", "1"])).unwrap(),
+            "answer 1 is 18446744073709551614\n"
+        );
+    }
+
+    #[test]
+    fn it_prints_csv() {
+        let csv = run(TEST_INPUT_TWO, &options(&["--csv"])).unwrap();
+        assert_eq!(csv.lines().next(), Some("field_name,column_index,my_value"));
+        assert_eq!(csv.lines().count(), 4);
+    }
+
+    #[test]
+    fn it_emits_the_valid_tickets() {
+        assert_eq!(
+            run(TEST_INPUT, &options(&["--emit-valid"])).unwrap(),
+            "7,3,47\n"
+        );
+        assert_eq!(
+            run(TEST_INPUT_TWO, &options(&["--emit-valid"])).unwrap(),
+            "3,9,18\n15,1,5\n5,14,9\n"
+        );
+    }
+
+    #[test]
+    fn it_prints_a_one_based_table() {
+        assert_eq!(
+            run(TEST_INPUT_TWO, &options(&["--table", "--one-based"])).unwrap(),
+            indoc! {"
+                column  field  value
+                     1  row    11
+                     2  class  12
+                     3  seat   13
+            "}
+        );
+    }
+
+    const AMBIGUOUS_INPUT: &str = indoc! {"
+        a: 1-3
+        b: 1-3
+
+        your ticket:
+        1,2
+
+        nearby tickets:
+        1,2
+    "};
+
+    #[test]
+    fn it_reports_an_ambiguous_solve_for_every_format() {
+        for flag in &["--table", "--json", "--csv"] {
+            match run(AMBIGUOUS_INPUT, &options(&[flag])) {
+                Err(Error::Solve(SolveError::Ambiguous)) => {}
+                other => panic!("expected a solve error for {}, got {:?}", flag, other),
+            }
+        }
+    }
+
+    #[test]
+    fn it_reports_an_ambiguous_breakdown() {
+        match run(AMBIGUOUS_INPUT, &options(&["--part", "2", "--breakdown"])) {
+            Err(Error::Solve(SolveError::Ambiguous)) => {}
+            other => panic!("expected a solve error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_reports_an_ambiguous_part_two() {
+        for (input, args) in &[
+            (AMBIGUOUS_INPUT, &["--part", "2"][..]),
+            (TEST_INPUT_TWO, &["--limit", "1"]),
+        ] {
+            match run(input, &options(args)) {
+                Err(Error::Solve(SolveError::Ambiguous)) => {}
+                other => panic!("expected a solve error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn it_reports_a_short_ticket_of_mine() {
+        let input = TEST_INPUT_TWO
+            .replace("seat:", "departure seat:")
+            .replace("11,12,13", "11,12");
+        match run(&input, &options(&["--part", "2"])) {
+            Err(err @ Error::Solve(_)) => assert_eq!(
+                err.to_string(),
+                "could not determine fields: your ticket 1 has 2 fields, expected 3"
+            ),
+            other => panic!("expected a solve error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_reports_parse_errors() {
+        match run("class: 1-3 or 5-7\n", &Options::default()) {
+            Err(err @ Error::Parse(_)) => assert_eq!(
+                err.to_string(),
+                "could not parse input: line 2: missing your ticket section"
+            ),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_checks_a_good_input() {
+        let (code, report) = check(include_str!("../input.txt"));
+        assert_eq!(code, 0);
+        assert!(report.starts_with("ok: 20 rules"));
+    }
+
+    #[test]
+    fn it_fails_a_broken_input() {
+        let (code, report) = check(indoc! {"
+            class: 1-3 or 5-7
+            class: 1-3 or 5-7
+
+            your ticket:
+            1
+
+            nearby tickets:
+            1
+        "});
+        assert_eq!(code, 1);
+        assert_eq!(
+            report,
+            indoc! {"
+                invalid: rule class is defined twice
+                invalid: your ticket 1 has 1 fields, expected 2
+                invalid: nearby ticket 1 has 1 fields, expected 2
+            "}
+        );
+
+        let (code, _) = check("class: 1-3 or 5-7\n");
+        assert_eq!(code, 1);
+    }
 }
@@ -0,0 +1,222 @@
+use crate::{Notes, SolveError};
+
+/// Presentation settings for the solution formatters. Columns are always 0
+/// based internally; `one_based` only changes how they are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FormatOptions {
+    pub one_based: bool,
+}
+
+impl FormatOptions {
+    fn column(&self, column: usize) -> usize {
+        if self.one_based {
+            column + 1
+        } else {
+            column
+        }
+    }
+}
+
+// The assignment ordered by column, with my ticket's value for each field.
+fn rows<'a>(
+    notes: &Notes,
+    assignment: &'a [(usize, String)],
+) -> Result<Vec<(usize, &'a str, i64)>, SolveError> {
+    let passport = notes.my_passport().ok_or(SolveError::NoTicket)?;
+    let mut rows: Vec<(usize, &str, i64)> = assignment
+        .iter()
+        .map(|(column, name)| (*column, name.as_str(), passport[*column]))
+        .collect();
+    rows.sort_unstable();
+    Ok(rows)
+}
+
+/// A table of each column, the field assigned to it and my ticket's value.
+/// Each formatter fails with `SolveError::NoTicket` when I have no ticket.
+pub fn format_solution(
+    notes: &Notes,
+    assignment: &[(usize, String)],
+    options: &FormatOptions,
+) -> Result<String, SolveError> {
+    let rows = rows(notes, assignment)?;
+    let width = rows
+        .iter()
+        .map(|(_, name, _)| name.len())
+        .chain(Some("field".len()))
+        .max()
+        .unwrap_or(0);
+    let mut output = format!("column  {:<width$}  value\n", "field", width = width);
+    for (column, name, value) in rows {
+        output.push_str(&format!(
+            "{:>6}  {:<width$}  {}\n",
+            options.column(column),
+            name,
+            value,
+            width = width
+        ));
+    }
+    Ok(output)
+}
+
+/// The solution as a JSON object with a `fields` array.
+pub fn format_solution_json(
+    notes: &Notes,
+    assignment: &[(usize, String)],
+    options: &FormatOptions,
+) -> Result<String, SolveError> {
+    let fields: Vec<String> = rows(notes, assignment)?
+        .into_iter()
+        .map(|(column, name, value)| {
+            format!(
+                r#"{{"column":{},"name":{},"value":{}}}"#,
+                options.column(column),
+                json_string(name),
+                value
+            )
+        })
+        .collect();
+    Ok(format!(r#"{{"fields":[{}]}}"#, fields.join(",")))
+}
+
+/// The solution as CSV with a header row, one row per field.
+pub fn format_solution_csv(
+    notes: &Notes,
+    assignment: &[(usize, String)],
+    options: &FormatOptions,
+) -> Result<String, SolveError> {
+    let mut output = String::from("field_name,column_index,my_value\n");
+    for (column, name, value) in rows(notes, assignment)? {
+        output.push_str(&format!(
+            "{},{},{}\n",
+            csv_field(name),
+            options.column(column),
+            value
+        ));
+    }
+    Ok(output)
+}
+
+// Quotes a field containing a separator, quote or line break, doubling any
+// quotes inside it.
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut output = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solve;
+
+    use indoc::indoc;
+
+    const TEST_INPUT_TWO: &str = indoc! {"
+        class: 0-1 or 4-19
+        row: 0-5 or 8-19
+        seat: 0-13 or 16-19
+
+        your ticket:
+        11,12,13
+
+        nearby tickets:
+        3,9,18
+        15,1,5
+        5,14,9
+    "};
+
+    #[test]
+    fn it_formats_a_zero_based_table() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        assert_eq!(
+            format_solution(&notes, &assignment, &FormatOptions::default()).unwrap(),
+            indoc! {"
+                column  field  value
+                     0  row    11
+                     1  class  12
+                     2  seat   13
+            "}
+        );
+    }
+
+    #[test]
+    fn it_formats_a_one_based_table() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        let options = FormatOptions { one_based: true };
+        let table = format_solution(&notes, &assignment, &options).unwrap();
+        let columns: Vec<&str> = table
+            .lines()
+            .skip(1)
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(columns, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn it_formats_json() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        let options = FormatOptions { one_based: true };
+        assert_eq!(
+            format_solution_json(&notes, &assignment, &options).unwrap(),
+            r#"{"fields":[{"column":1,"name":"row","value":11},{"column":2,"name":"class","value":12},{"column":3,"name":"seat","value":13}]}"#
+        );
+        assert_eq!(json_string("a \"b\"\\"), r#""a \"b\"\\""#);
+    }
+
+    #[test]
+    fn it_formats_csv() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        assert_eq!(
+            format_solution_csv(&notes, &assignment, &FormatOptions::default()).unwrap(),
+            indoc! {"
+                field_name,column_index,my_value
+                row,0,11
+                class,1,12
+                seat,2,13
+            "}
+        );
+        assert_eq!(csv_field("seat"), "seat");
+        assert_eq!(csv_field("row, \"a\""), r#""row, ""a""""#);
+    }
+
+    #[test]
+    fn it_needs_a_ticket_of_mine() {
+        let mut notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let assignment = solve(&notes).unwrap();
+        notes.my_passports.clear();
+        let options = FormatOptions::default();
+        assert_eq!(
+            format_solution(&notes, &assignment, &options),
+            Err(SolveError::NoTicket)
+        );
+        assert_eq!(
+            format_solution_json(&notes, &assignment, &options),
+            Err(SolveError::NoTicket)
+        );
+        assert_eq!(
+            format_solution_csv(&notes, &assignment, &options),
+            Err(SolveError::NoTicket)
+        );
+    }
+}
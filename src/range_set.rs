@@ -0,0 +1,202 @@
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+/// A set of `i64` values held as sorted, disjoint and non-adjacent inclusive
+/// ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RangeSet {
+    ranges: Vec<RangeInclusive<i64>>,
+}
+
+impl RangeSet {
+    pub fn new() -> RangeSet {
+        RangeSet::default()
+    }
+
+    pub fn from_ranges<'a>(ranges: impl IntoIterator<Item = &'a RangeInclusive<i64>>) -> RangeSet {
+        let mut set = RangeSet::new();
+        for range in ranges {
+            set.insert(range.clone());
+        }
+        set
+    }
+
+    pub fn ranges(&self) -> &[RangeInclusive<i64>] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The number of values in the set. A `u128` is used so that a set covering
+    /// the whole of `i64` is counted without overflow.
+    pub fn len(&self) -> u128 {
+        self.ranges
+            .iter()
+            .map(|range| (*range.end() as i128 - *range.start() as i128 + 1) as u128)
+            .sum()
+    }
+
+    pub fn contains(&self, value: &i64) -> bool {
+        self.ranges
+            .binary_search_by(|range| {
+                if range.end() < value {
+                    Ordering::Less
+                } else if range.start() > value {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn insert(&mut self, range: RangeInclusive<i64>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut start, mut end) = range.into_inner();
+        let mut merged = Vec::with_capacity(self.ranges.len() + 1);
+        let mut placed = false;
+        for existing in self.ranges.drain(..) {
+            // Ranges which overlap or touch the new one are absorbed into it.
+            if (*existing.end() as i128) < start as i128 - 1 {
+                merged.push(existing);
+            } else if *existing.start() as i128 > end as i128 + 1 {
+                if !placed {
+                    merged.push(start..=end);
+                    placed = true;
+                }
+                merged.push(existing);
+            } else {
+                start = start.min(*existing.start());
+                end = end.max(*existing.end());
+            }
+        }
+        if !placed {
+            merged.push(start..=end);
+        }
+        self.ranges = merged;
+    }
+
+    pub fn union(&self, other: &RangeSet) -> RangeSet {
+        let mut set = self.clone();
+        for range in &other.ranges {
+            set.insert(range.clone());
+        }
+        set
+    }
+
+    /// The values between the lowest and highest in the set which it does not
+    /// contain.
+    pub fn gaps(&self) -> Vec<RangeInclusive<i64>> {
+        // Neighbouring ranges never touch, so there is always at least one
+        // value between them and neither bound can overflow.
+        self.ranges
+            .windows(2)
+            .map(|pair| (*pair[0].end() + 1)..=(*pair[1].start() - 1))
+            .collect()
+    }
+
+    /// The values in this set but not in `other`.
+    pub fn difference(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = Vec::new();
+        for range in &self.ranges {
+            // `None` once a cut reaches i64::MAX, leaving nothing after it.
+            let mut start = Some(*range.start());
+            for cut in other
+                .ranges
+                .iter()
+                .filter(|cut| cut.start() <= range.end() && cut.end() >= range.start())
+            {
+                let from = match start {
+                    Some(from) => from,
+                    None => break,
+                };
+                if *cut.start() > from {
+                    ranges.push(from..=(*cut.start() - 1));
+                }
+                start = cut.end().checked_add(1);
+            }
+            if let Some(from) = start {
+                if from <= *range.end() {
+                    ranges.push(from..=*range.end());
+                }
+            }
+        }
+        RangeSet { ranges }
+    }
+
+    /// The values in exactly one of the two sets.
+    pub fn symmetric_difference(&self, other: &RangeSet) -> RangeSet {
+        self.difference(other).union(&other.difference(self))
+    }
+
+    pub fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = *a.start().max(b.start());
+            let end = *a.end().min(b.end());
+            if start <= end {
+                ranges.push(start..=end);
+            }
+            if a.end() < b.end() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        RangeSet { ranges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_merges_overlapping_and_adjacent_ranges() {
+        let set = RangeSet::from_ranges(&[5..=7, 1..=3, 4..=4, 10..=12, 11..=20]);
+        assert_eq!(set.ranges(), &[1..=7, 10..=20]);
+        assert_eq!(set.len(), 18);
+        assert!(set.contains(&4));
+        assert!(!set.contains(&8));
+    }
+
+    #[test]
+    fn it_handles_the_limits_of_i64() {
+        let set = RangeSet::from_ranges(&[i64::MIN..=-1, 0..=i64::MAX]);
+        assert_eq!(set.ranges(), &[i64::MIN..=i64::MAX]);
+        assert_eq!(set.len(), 1 << 64);
+    }
+
+    #[test]
+    fn it_finds_the_gaps_between_ranges() {
+        let set = RangeSet::from_ranges(&[1..=3, 5..=7, 10..=12, i64::MAX..=i64::MAX]);
+        assert_eq!(set.gaps(), vec![4..=4, 8..=9, 13..=i64::MAX - 1]);
+        assert!(RangeSet::from_ranges(&[1..=3]).gaps().is_empty());
+    }
+
+    #[test]
+    fn it_intersects_sets() {
+        let a = RangeSet::from_ranges(&[1..=5, 10..=15]);
+        let b = RangeSet::from_ranges(&[4..=11, 15..=20]);
+        assert_eq!(a.intersection(&b).ranges(), &[4..=5, 10..=11, 15..=15]);
+        assert_eq!(a.union(&b).ranges(), &[1..=20]);
+    }
+
+    #[test]
+    fn it_subtracts_sets() {
+        let a = RangeSet::from_ranges(&[1..=10, 20..=i64::MAX]);
+        let b = RangeSet::from_ranges(&[3..=4, 8..=22, 30..=i64::MAX]);
+        assert_eq!(a.difference(&b).ranges(), &[1..=2, 5..=7, 23..=29]);
+        assert_eq!(
+            a.symmetric_difference(&b).ranges(),
+            &[1..=2, 5..=7, 11..=19, 23..=29]
+        );
+        assert!(a.difference(&a).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use indoc::indoc;
+
+/// One of the puzzle's worked examples and its known answers, for checking a
+/// solver without repeating the numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleAnswers {
+    pub input: &'static str,
+    /// The sum of the values no rule accepts.
+    pub error_rate: i64,
+    /// The product of my departure fields. Neither sample has any, so this is
+    /// the empty product.
+    pub departure_product: i64,
+    /// The field in each column.
+    pub columns: [&'static str; 3],
+}
+
+const SAMPLE_ANSWERS: [SampleAnswers; 2] = [
+    SampleAnswers {
+        input: indoc! {"
+            class: 1-3 or 5-7
+            row: 6-11 or 33-44
+            seat: 13-40 or 45-50
+
+            your ticket:
+            7,1,14
+
+            nearby tickets:
+            7,3,47
+            40,4,50
+            55,2,20
+            38,6,12
+        "},
+        error_rate: 71,
+        departure_product: 1,
+        columns: ["row", "class", "seat"],
+    },
+    SampleAnswers {
+        input: indoc! {"
+            class: 0-1 or 4-19
+            row: 0-5 or 8-19
+            seat: 0-13 or 16-19
+
+            your ticket:
+            11,12,13
+
+            nearby tickets:
+            3,9,18
+            15,1,5
+            5,14,9
+        "},
+        error_rate: 0,
+        departure_product: 1,
+        columns: ["row", "class", "seat"],
+    },
+];
+
+/// The two examples from the puzzle text, in order.
+pub fn sample_answers() -> &'static [SampleAnswers] {
+    &SAMPLE_ANSWERS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{departure_product, find_all_invalid_fields, solve_to_columns, Notes};
+
+    #[test]
+    fn the_samples_give_their_answers() {
+        for sample in sample_answers() {
+            let notes = Notes::parse(sample.input).unwrap();
+            let error_rate: i64 = find_all_invalid_fields(&notes.near_passports, &notes.rules)
+                .into_iter()
+                .sum();
+            assert_eq!(error_rate, sample.error_rate);
+            assert_eq!(departure_product(&notes), Ok(sample.departure_product));
+            assert_eq!(solve_to_columns(&notes).unwrap(), sample.columns);
+        }
+    }
+}
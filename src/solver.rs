@@ -0,0 +1,408 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    NoCandidates(usize),
+    Ambiguous,
+    Unsolvable,
+    UnknownField(String),
+    TooManyCandidates {
+        limit: usize,
+    },
+    ConflictingHint(String),
+    /// The sorted candidate set sizes are not the chain 1, 2, ... n which
+    /// `StrictChain` requires.
+    UnexpectedCandidateShape {
+        sizes: Vec<usize>,
+    },
+    /// A nearby ticket does not have one field for each rule.
+    TicketLength {
+        ticket: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// One of my tickets does not have one field for each rule.
+    MyTicketLength {
+        ticket: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A product was to be reduced modulo a number which is not positive.
+    InvalidModulus(i64),
+    /// There is no ticket of mine to read the answers from.
+    NoTicket,
+    /// An answer combining my ticket's values does not fit in an `i64`.
+    Overflow,
+    /// No field starts with the prefix, so there is nothing to take the
+    /// maximum or minimum of.
+    NoMatchingFields(String),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveError::NoCandidates(rule) => write!(f, "rule {} fits no column", rule),
+            SolveError::Ambiguous => write!(f, "more than one assignment is possible"),
+            SolveError::Unsolvable => write!(f, "no assignment is possible"),
+            SolveError::UnknownField(name) => write!(f, "there is no field named {}", name),
+            SolveError::ConflictingHint(name) => {
+                write!(f, "the hint for {} conflicts with the candidates", name)
+            }
+            SolveError::TooManyCandidates { limit } => {
+                write!(f, "more than {} candidate positions", limit)
+            }
+            SolveError::TicketLength {
+                ticket,
+                expected,
+                found,
+            } => write!(
+                f,
+                "nearby ticket {} has {} fields, expected {}",
+                ticket + 1,
+                found,
+                expected
+            ),
+            SolveError::MyTicketLength {
+                ticket,
+                expected,
+                found,
+            } => write!(
+                f,
+                "your ticket {} has {} fields, expected {}",
+                ticket + 1,
+                found,
+                expected
+            ),
+            SolveError::UnexpectedCandidateShape { sizes } => write!(
+                f,
+                "candidate set sizes {:?} do not run from 1 to {}",
+                sizes,
+                sizes.len()
+            ),
+            SolveError::InvalidModulus(modulus) => {
+                write!(f, "the modulus {} is not positive", modulus)
+            }
+            SolveError::NoTicket => write!(f, "there is no ticket of yours"),
+            SolveError::Overflow => write!(f, "the answer is too large"),
+            SolveError::NoMatchingFields(prefix) => {
+                write!(f, "no field starts with {}", prefix)
+            }
+        }
+    }
+}
+
+impl Error for SolveError {}
+
+/// An algorithm assigning each rule to a column. `candidates[rule]` lists the
+/// columns the rule may occupy and the result holds the chosen column for each
+/// rule, in the same order.
+pub trait Solver {
+    fn solve(&self, candidates: &[Vec<usize>]) -> Result<Vec<usize>, SolveError>;
+}
+
+fn column_count(candidates: &[Vec<usize>]) -> usize {
+    candidates
+        .iter()
+        .flatten()
+        .map(|column| column + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+fn check_candidates(candidates: &[Vec<usize>]) -> Result<(), SolveError> {
+    match candidates.iter().position(|columns| columns.is_empty()) {
+        Some(rule) => Err(SolveError::NoCandidates(rule)),
+        None => Ok(()),
+    }
+}
+
+/// Repeatedly fixes any rule left with a single free column. Fails with
+/// `SolveError::Ambiguous` when no rule is forced.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyElimination;
+
+impl Solver for GreedyElimination {
+    fn solve(&self, candidates: &[Vec<usize>]) -> Result<Vec<usize>, SolveError> {
+        check_candidates(candidates)?;
+        let mut assignment: Vec<Option<usize>> = vec![None; candidates.len()];
+        let mut taken = vec![false; column_count(candidates)];
+        for _ in 0..candidates.len() {
+            let mut forced = None;
+            for (rule, columns) in candidates.iter().enumerate() {
+                if assignment[rule].is_some() {
+                    continue;
+                }
+                let mut free = columns.iter().filter(|column| !taken[**column]);
+                match (free.next(), free.next()) {
+                    (None, _) => return Err(SolveError::Unsolvable),
+                    (Some(column), None) if forced.is_none() => forced = Some((rule, *column)),
+                    _ => {}
+                }
+            }
+            let (rule, column) = forced.ok_or(SolveError::Ambiguous)?;
+            assignment[rule] = Some(column);
+            taken[column] = true;
+        }
+        Ok(assignment.into_iter().flatten().collect())
+    }
+}
+
+/// Finds a perfect matching with augmenting paths. Always succeeds when any
+/// assignment exists but picks one arbitrarily when several do.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BipartiteMatching;
+
+impl BipartiteMatching {
+    fn augment(
+        rule: usize,
+        candidates: &[Vec<usize>],
+        owner: &mut [Option<usize>],
+        seen: &mut [bool],
+    ) -> bool {
+        for &column in &candidates[rule] {
+            if seen[column] {
+                continue;
+            }
+            seen[column] = true;
+            let free = match owner[column] {
+                None => true,
+                Some(other) => BipartiteMatching::augment(other, candidates, owner, seen),
+            };
+            if free {
+                owner[column] = Some(rule);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Solver for BipartiteMatching {
+    fn solve(&self, candidates: &[Vec<usize>]) -> Result<Vec<usize>, SolveError> {
+        check_candidates(candidates)?;
+        let mut owner: Vec<Option<usize>> = vec![None; column_count(candidates)];
+        for rule in 0..candidates.len() {
+            let mut seen = vec![false; owner.len()];
+            if !BipartiteMatching::augment(rule, candidates, &mut owner, &mut seen) {
+                return Err(SolveError::Unsolvable);
+            }
+        }
+        let mut assignment = vec![0; candidates.len()];
+        for (column, rule) in owner.into_iter().enumerate() {
+            if let Some(rule) = rule {
+                assignment[rule] = column;
+            }
+        }
+        Ok(assignment)
+    }
+}
+
+/// Depth first search, always expanding the rule with the fewest free columns.
+/// Returns the first complete assignment found.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Backtracking;
+
+impl Backtracking {
+    fn search(
+        candidates: &[Vec<usize>],
+        assignment: &mut Vec<Option<usize>>,
+        taken: &mut Vec<bool>,
+    ) -> bool {
+        let next = (0..candidates.len())
+            .filter(|rule| assignment[*rule].is_none())
+            .min_by_key(|rule| {
+                candidates[*rule]
+                    .iter()
+                    .filter(|column| !taken[**column])
+                    .count()
+            });
+        let rule = match next {
+            Some(rule) => rule,
+            None => return true,
+        };
+        for &column in &candidates[rule] {
+            if taken[column] {
+                continue;
+            }
+            assignment[rule] = Some(column);
+            taken[column] = true;
+            if Backtracking::search(candidates, assignment, taken) {
+                return true;
+            }
+            assignment[rule] = None;
+            taken[column] = false;
+        }
+        false
+    }
+}
+
+impl Solver for Backtracking {
+    fn solve(&self, candidates: &[Vec<usize>]) -> Result<Vec<usize>, SolveError> {
+        check_candidates(candidates)?;
+        let mut assignment = vec![None; candidates.len()];
+        let mut taken = vec![false; column_count(candidates)];
+        if Backtracking::search(candidates, &mut assignment, &mut taken) {
+            Ok(assignment.into_iter().flatten().collect())
+        } else {
+            Err(SolveError::Unsolvable)
+        }
+    }
+}
+
+/// The original solver, which relied on the rules having 1, 2, ... n candidate
+/// columns so that taking them smallest first always leaves exactly one free
+/// column. Any other shape fails with `SolveError::UnexpectedCandidateShape`
+/// rather than being solved some other way.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StrictChain;
+
+impl Solver for StrictChain {
+    fn solve(&self, candidates: &[Vec<usize>]) -> Result<Vec<usize>, SolveError> {
+        let mut order: Vec<usize> = (0..candidates.len()).collect();
+        order.sort_by_key(|rule| candidates[*rule].len());
+        let sizes: Vec<usize> = order.iter().map(|rule| candidates[*rule].len()).collect();
+        if sizes.iter().enumerate().any(|(i, size)| *size != i + 1) {
+            return Err(SolveError::UnexpectedCandidateShape { sizes });
+        }
+        let mut assignment = vec![0; candidates.len()];
+        let mut taken = vec![false; column_count(candidates)];
+        for rule in order {
+            let mut free = candidates[rule].iter().filter(|column| !taken[**column]);
+            match (free.next(), free.next()) {
+                (Some(column), None) => {
+                    assignment[rule] = *column;
+                    taken[*column] = true;
+                }
+                (None, _) => return Err(SolveError::Unsolvable),
+                _ => return Err(SolveError::Ambiguous),
+            }
+        }
+        Ok(assignment)
+    }
+}
+
+/// Enumerates complete assignments, stopping once `cap` have been found.
+pub fn all_assignments(candidates: &[Vec<usize>], cap: usize) -> Vec<Vec<usize>> {
+    fn enumerate(
+        rule: usize,
+        candidates: &[Vec<usize>],
+        cap: usize,
+        assignment: &mut Vec<usize>,
+        taken: &mut Vec<bool>,
+        found: &mut Vec<Vec<usize>>,
+    ) {
+        if found.len() >= cap {
+            return;
+        }
+        if rule == candidates.len() {
+            found.push(assignment.clone());
+            return;
+        }
+        for &column in &candidates[rule] {
+            if taken[column] {
+                continue;
+            }
+            taken[column] = true;
+            assignment.push(column);
+            enumerate(rule + 1, candidates, cap, assignment, taken, found);
+            assignment.pop();
+            taken[column] = false;
+        }
+    }
+
+    let mut found = Vec::new();
+    enumerate(
+        0,
+        candidates,
+        cap,
+        &mut Vec::new(),
+        &mut vec![false; column_count(candidates)],
+        &mut found,
+    );
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategies() -> Vec<Box<dyn Solver>> {
+        vec![
+            Box::new(GreedyElimination),
+            Box::new(BipartiteMatching),
+            Box::new(Backtracking),
+        ]
+    }
+
+    #[test]
+    fn all_strategies_agree_on_a_unique_solution() {
+        let candidates = vec![vec![1, 2], vec![0, 1, 2], vec![2]];
+        for solver in strategies() {
+            assert_eq!(solver.solve(&candidates), Ok(vec![1, 0, 2]));
+        }
+    }
+
+    #[test]
+    fn all_strategies_agree_on_a_longer_chain() {
+        let candidates = vec![vec![0, 1, 3], vec![1, 2], vec![2, 3], vec![3]];
+        for solver in strategies() {
+            assert_eq!(solver.solve(&candidates), Ok(vec![0, 1, 2, 3]));
+        }
+    }
+
+    #[test]
+    fn all_strategies_reject_an_empty_candidate_set() {
+        let candidates = vec![vec![0, 1], vec![], vec![1]];
+        for solver in strategies() {
+            assert_eq!(solver.solve(&candidates), Err(SolveError::NoCandidates(1)));
+        }
+    }
+
+    #[test]
+    fn all_strategies_reject_an_impossible_assignment() {
+        let candidates = vec![vec![0], vec![0], vec![1, 2]];
+        for solver in strategies() {
+            assert_eq!(solver.solve(&candidates), Err(SolveError::Unsolvable));
+        }
+    }
+
+    #[test]
+    fn it_enumerates_assignments_up_to_the_cap() {
+        let candidates = vec![vec![0, 1, 2], vec![0, 1, 2], vec![0, 1, 2]];
+        assert_eq!(all_assignments(&candidates, 100).len(), 6);
+        assert_eq!(
+            all_assignments(&candidates, 2),
+            vec![vec![0, 1, 2], vec![0, 2, 1]]
+        );
+    }
+
+    #[test]
+    fn the_strict_chain_requires_one_to_n_candidates() {
+        let candidates = vec![vec![1, 2], vec![0, 1, 2], vec![2]];
+        assert_eq!(StrictChain.solve(&candidates), Ok(vec![1, 0, 2]));
+
+        let candidates = vec![vec![0, 1, 3], vec![1, 2], vec![2, 3], vec![3]];
+        assert_eq!(
+            StrictChain.solve(&candidates),
+            Err(SolveError::UnexpectedCandidateShape {
+                sizes: vec![1, 2, 2, 3]
+            })
+        );
+        assert_eq!(
+            StrictChain.solve(&[vec![0], vec![1, 2]]),
+            Err(SolveError::Ambiguous)
+        );
+    }
+
+    #[test]
+    fn only_greedy_elimination_rejects_an_ambiguous_assignment() {
+        let candidates = vec![vec![0, 1], vec![0, 1]];
+        assert_eq!(
+            GreedyElimination.solve(&candidates),
+            Err(SolveError::Ambiguous)
+        );
+        assert!(BipartiteMatching.solve(&candidates).is_ok());
+        assert!(Backtracking.solve(&candidates).is_ok());
+    }
+}
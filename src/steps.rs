@@ -0,0 +1,116 @@
+/// One elimination: `field` is fixed to `column`, which is then removed from
+/// the candidates of each of the fields in `removed_from`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveStep {
+    pub field: String,
+    pub column: usize,
+    pub removed_from: Vec<String>,
+}
+
+struct SolveSteps {
+    remaining: Vec<(String, Vec<usize>)>,
+}
+
+impl Iterator for SolveSteps {
+    type Item = SolveStep;
+
+    fn next(&mut self) -> Option<SolveStep> {
+        let forced = self
+            .remaining
+            .iter()
+            .position(|(_, columns)| columns.len() == 1)?;
+        let (field, columns) = self.remaining.remove(forced);
+        let column = columns[0];
+        let removed_from = self
+            .remaining
+            .iter_mut()
+            .filter_map(|(name, columns)| {
+                let before = columns.len();
+                columns.retain(|other| *other != column);
+                if columns.len() < before {
+                    Some(name.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Some(SolveStep {
+            field,
+            column,
+            removed_from,
+        })
+    }
+}
+
+/// The steps of solving by elimination, one per field fixed. The steps stop
+/// early when no field is left with a single column, so an ambiguous input
+/// yields fewer steps than it has fields.
+pub fn solve_steps(valid_positions: Vec<Vec<(usize, String)>>) -> impl Iterator<Item = SolveStep> {
+    SolveSteps {
+        remaining: valid_positions
+            .into_iter()
+            .filter(|positions| !positions.is_empty())
+            .map(|positions| {
+                let name = positions[0].1.clone();
+                (
+                    name,
+                    positions.into_iter().map(|(column, _)| column).collect(),
+                )
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{filter_invalid, find_all_valid_positions, solve, Notes};
+
+    use indoc::indoc;
+
+    const TEST_INPUT_TWO: &str = indoc! {"
+        class: 0-1 or 4-19
+        row: 0-5 or 8-19
+        seat: 0-13 or 16-19
+
+        your ticket:
+        11,12,13
+
+        nearby tickets:
+        3,9,18
+        15,1,5
+        5,14,9
+    "};
+
+    #[test]
+    fn it_yields_each_elimination_step() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+        let valid_positions =
+            find_all_valid_positions(&notes.rules, &valid_passports, notes.num_columns());
+        let steps: Vec<SolveStep> = solve_steps(valid_positions).collect();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(
+            steps[0],
+            SolveStep {
+                field: "seat".to_string(),
+                column: 2,
+                removed_from: vec!["class".to_string(), "row".to_string()],
+            }
+        );
+        let assignment: Vec<(usize, String)> = steps
+            .into_iter()
+            .map(|step| (step.column, step.field))
+            .collect();
+        assert_eq!(Ok(assignment), solve(&notes));
+    }
+
+    #[test]
+    fn it_stops_when_no_field_is_forced() {
+        let valid_positions = vec![
+            vec![(0, "a".to_string()), (1, "a".to_string())],
+            vec![(0, "b".to_string()), (1, "b".to_string())],
+        ];
+        assert_eq!(solve_steps(valid_positions).count(), 0);
+    }
+}
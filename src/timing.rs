@@ -0,0 +1,100 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::valid_positions_ignoring;
+use crate::{check_ticket_widths, determine_field_positions, filter_invalid};
+use crate::{Notes, SolveError, DEFAULT_MAX_CANDIDATES};
+
+/// How long each phase of a solve took. `solve_timed` starts from notes which
+/// are already parsed, so `parse` is left for the caller to fill in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolveTimings {
+    pub parse: Duration,
+    pub filter: Duration,
+    pub candidates: Duration,
+    pub assign: Duration,
+}
+
+impl SolveTimings {
+    pub fn total(&self) -> Duration {
+        self.parse + self.filter + self.candidates + self.assign
+    }
+}
+
+impl fmt::Display for SolveTimings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "parse: {:?}", self.parse)?;
+        writeln!(f, "filter: {:?}", self.filter)?;
+        writeln!(f, "candidates: {:?}", self.candidates)?;
+        writeln!(f, "assign: {:?}", self.assign)?;
+        writeln!(f, "total: {:?}", self.total())
+    }
+}
+
+/// Solves the notes as `solve` does, timing each phase after parsing.
+pub fn solve_timed(notes: &Notes) -> Result<(Vec<(usize, String)>, SolveTimings), SolveError> {
+    let mut timings = SolveTimings::default();
+
+    check_ticket_widths(notes)?;
+    let start = Instant::now();
+    let valid_passports = filter_invalid(notes.near_passports.clone(), &notes.rules);
+    timings.filter = start.elapsed();
+
+    let start = Instant::now();
+    let valid_positions = valid_positions_ignoring(
+        &notes.rules,
+        &valid_passports,
+        notes.num_columns(),
+        &[],
+        DEFAULT_MAX_CANDIDATES,
+    )?;
+    timings.candidates = start.elapsed();
+
+    let start = Instant::now();
+    let determined_positions = determine_field_positions(valid_positions)?;
+    timings.assign = start.elapsed();
+
+    Ok((determined_positions, timings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solve;
+
+    use indoc::indoc;
+
+    const TEST_INPUT_TWO: &str = indoc! {"
+        class: 0-1 or 4-19
+        row: 0-5 or 8-19
+        seat: 0-13 or 16-19
+
+        your ticket:
+        11,12,13
+
+        nearby tickets:
+        3,9,18
+        15,1,5
+        5,14,9
+    "};
+
+    #[test]
+    fn it_times_every_phase_of_a_solve() {
+        let notes = Notes::parse(TEST_INPUT_TWO).unwrap();
+        let (assignment, timings) = solve_timed(&notes).unwrap();
+        assert_eq!(Ok(assignment), solve(&notes));
+        assert_eq!(timings.parse, Duration::default());
+        assert!(timings.total() >= timings.candidates.max(timings.assign));
+        let report = timings.to_string();
+        for phase in &["parse", "filter", "candidates", "assign", "total"] {
+            assert!(report.contains(&format!("{}: ", phase)));
+        }
+    }
+
+    #[test]
+    fn it_reports_a_failed_solve() {
+        let notes =
+            Notes::parse("a: 1-3\nb: 1-3\n\nyour ticket:\n1,2\n\nnearby tickets:\n1,2\n").unwrap();
+        assert_eq!(solve_timed(&notes), Err(SolveError::Ambiguous));
+    }
+}